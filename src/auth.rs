@@ -0,0 +1,163 @@
+
+use actix_web::{HttpMessage, HttpRequest, http};
+use actix_web::cookie::{Cookie, SameSite};
+use chrono::Utc;
+use juniper::FieldError;
+use jwt_simple::JWTError;
+use jwt_simple::prelude::*;
+use thiserror::Error;
+
+use crate::common::{VoteTokenClaim, gateway_error};
+use crate::config::config;
+use crate::jwks::KeySet;
+
+/// 存放token的 HttpOnly cookie
+pub const VOTE_TOKEN_COOKIE: &'static str = "vote_token";
+pub const SESSION_TOKEN_COOKIE: &'static str = "session_token";
+
+/// 使用 cookie 中的token时必须带上的请求头，跨站请求无法在不经过 CORS 预检的情况下设置它
+pub const CSRF_HEADER: &'static str = "X-Requested-With";
+
+/// 投票token的 audience
+pub const VOTE_TOKEN_AUDIENCE: &'static str = "vote";
+
+/// 通过投票token验证后的投票人
+#[derive(Debug, Clone)]
+pub struct VoterIdentity {
+	pub vote_id: String,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+	#[error("vote token is missing")]
+	Missing,
+	#[error("session token is missing")]
+	SessionMissing,
+	#[error("vote token has expired")]
+	Expired,
+	#[error("vote token is not issued for voting")]
+	WrongAudience,
+	#[error("vote token is invalid")]
+	Invalid,
+}
+
+impl TokenError {
+	pub fn code(&self) -> &'static str {
+		match self {
+			TokenError::Missing | TokenError::SessionMissing => "TOKEN_MISSING",
+			TokenError::Expired => "TOKEN_EXPIRED",
+			TokenError::WrongAudience => "TOKEN_WRONG_AUDIENCE",
+			TokenError::Invalid => "TOKEN_INVALID",
+		}
+	}
+
+	pub fn into_field_error(self) -> FieldError {
+		gateway_error(self.code(), &self.to_string())
+	}
+}
+
+/// 验证投票token的签名、有效期和 audience，并取出 vote_id
+pub fn verify_vote_token(keys: &KeySet, token: &str) -> Result<VoterIdentity, TokenError> {
+	let metadata = Token::decode_metadata(token).map_err(|_| TokenError::Invalid)?;
+	let key = keys.select(metadata.key_id()).ok_or(TokenError::Invalid)?;
+	let mut options = VerificationOptions::default();
+	options.allowed_audiences = Some(HashSet::from_strings(&[VOTE_TOKEN_AUDIENCE]));
+	let claims = key.verify_token::<VoteTokenClaim>(token, Some(options)).map_err(|e| {
+		match e.downcast_ref::<JWTError>() {
+			Some(JWTError::TokenHasExpired) => TokenError::Expired,
+			Some(JWTError::RequiredAudienceMismatch) | Some(JWTError::RequiredAudienceMissing) => TokenError::WrongAudience,
+			_ => TokenError::Invalid,
+		}
+	})?;
+	match claims.custom.vote_id {
+		Some(vote_id) if !vote_id.is_empty() => Ok(VoterIdentity { vote_id: vote_id }),
+		_ => Err(TokenError::Invalid),
+	}
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+	let value = req.headers().get(http::header::AUTHORIZATION)?.to_str().ok()?.trim();
+	let (scheme, token) = value.split_at(value.find(' ')?);
+	if scheme.eq_ignore_ascii_case("Bearer") && !token.trim().is_empty() {
+		Some(token.trim().to_string())
+	} else {
+		None
+	}
+}
+
+fn cookie_token(req: &HttpRequest, name: &str) -> Option<String> {
+	req.cookie(name).map(|c| c.value().to_string()).filter(|v| !v.is_empty())
+}
+
+/// 取出请求携带的 (投票token, 登录token)，Authorization: Bearer 优先于 cookie。
+/// Bearer token 按 audience 归为投票token或登录token，另一个仍从 cookie 读取。
+/// 没有 CSRF_HEADER 的请求不读取 cookie，防止其他网站借用户的 cookie 发起请求
+pub fn request_tokens(req: &HttpRequest) -> (Option<String>, Option<String>) {
+	let (bearer_vote, bearer_session) = match bearer_token(req) {
+		Some(token) if has_vote_audience(&token) => (Some(token), None),
+		Some(token) => (None, Some(token)),
+		None => (None, None),
+	};
+	let use_cookies = req.headers().contains_key(CSRF_HEADER);
+	let cookie = |name| if use_cookies { cookie_token(req, name) } else { None };
+	let vote_token = bearer_vote.or_else(|| cookie(VOTE_TOKEN_COOKIE));
+	let session_token = bearer_session.or_else(|| cookie(SESSION_TOKEN_COOKIE));
+	(vote_token, session_token)
+}
+
+#[derive(Deserialize)]
+struct UnverifiedClaims {
+	exp: Option<i64>,
+	aud: Option<serde_json::Value>,
+}
+
+/// 不校验签名读取token的 claims，只用于区分token类型和设置 cookie 的有效期
+fn unverified_claims(token: &str) -> Option<UnverifiedClaims> {
+	let claims = Base64UrlSafeNoPadding::decode_to_vec(token.split('.').nth(1)?, None).ok()?;
+	serde_json::from_slice(&claims).ok()
+}
+
+/// token的 audience 是否为投票，签名和有效期仍由 verify_vote_token 校验
+pub fn has_vote_audience(token: &str) -> bool {
+	match unverified_claims(token).and_then(|c| c.aud) {
+		Some(serde_json::Value::String(aud)) => aud == VOTE_TOKEN_AUDIENCE,
+		Some(serde_json::Value::Array(auds)) => auds.iter().any(|a| a.as_str() == Some(VOTE_TOKEN_AUDIENCE)),
+		_ => false,
+	}
+}
+
+/// token剩余的有效期（秒）
+fn token_expires_in(token: &str) -> Option<i64> {
+	let exp = unverified_claims(token)?.exp?;
+	Some((exp - Utc::now().timestamp()).max(0))
+}
+
+/// 按配置生成保存token的 cookie
+pub fn token_cookie(name: &'static str, value: String) -> Cookie<'static> {
+	let settings = &config().session_cookie;
+	let mut builder = Cookie::build(name, value.clone())
+		.path("/")
+		.http_only(true)
+		.secure(settings.secure)
+		.same_site(match settings.same_site.as_str() {
+			"Lax" => SameSite::Lax,
+			"None" => SameSite::None,
+			_ => SameSite::Strict,
+		});
+	if let Some(domain) = &settings.domain {
+		builder = builder.domain(domain.clone());
+	}
+	if settings.persistent {
+		if let Some(secs) = token_expires_in(&value) {
+			builder = builder.max_age(time::Duration::seconds(secs));
+		}
+	}
+	builder.finish()
+}
+
+/// 清除 cookie，path 和 domain 需要与设置时一致
+pub fn removal_cookie(name: &'static str) -> Cookie<'static> {
+	let mut cookie = token_cookie(name, String::new());
+	cookie.make_removal();
+	cookie
+}
@@ -0,0 +1,309 @@
+
+use std::collections::HashSet;
+
+use juniper::FieldResult;
+
+use crate::common::{ValidationIssue, validation_error};
+use crate::config::{BallotConfig, config};
+use crate::submit_handler::{CPSubmit, CharacterSubmit, MusicSubmit, WorkSubmit, canonical_cp_members};
+use crate::vote_data::{is_votable_character, is_votable_music, is_votable_work};
+
+/// 单个投票条目的通用部分
+struct BallotItem<'a> {
+	id: &'a str,
+	reason: Option<&'a str>,
+	first: bool,
+}
+
+/// 检查条目数量、本命数量、重复、是否可投以及理由长度
+fn check_items(field: &str, items: &[BallotItem], max_items: usize, max_reason_length: usize, is_votable: impl Fn(&str) -> bool, issues: &mut Vec<ValidationIssue>) {
+	if items.len() > max_items {
+		issues.push(ValidationIssue::new(field, "TOO_MANY", format!("at most {} entries can be voted", max_items)));
+	}
+	let mut seen = HashSet::new();
+	let mut first_seen = false;
+	for (i, item) in items.iter().enumerate() {
+		let item_field = format!("{}[{}]", field, i);
+		if !seen.insert(item.id) {
+			issues.push(ValidationIssue::new(item_field.as_str(), "DUPLICATE", format!("{:?} is voted more than once", item.id)));
+		} else if !is_votable(item.id) {
+			issues.push(ValidationIssue::new(item_field.as_str(), "UNKNOWN_ID", format!("{:?} is not votable", item.id)));
+		}
+		if item.first {
+			if first_seen {
+				issues.push(ValidationIssue::new(item_field.as_str(), "MULTIPLE_FIRST", "only one entry can be marked as first"));
+			}
+			first_seen = true;
+		}
+		if let Some(reason) = item.reason {
+			if reason.chars().count() > max_reason_length {
+				issues.push(ValidationIssue::new(item_field.as_str(), "REASON_TOO_LONG", format!("reason must not exceed {} characters", max_reason_length)));
+			}
+		}
+	}
+}
+
+fn into_result(issues: Vec<ValidationIssue>) -> FieldResult<()> {
+	if issues.is_empty() {
+		Ok(())
+	} else {
+		Err(validation_error("INVALID_BALLOT", "ballot violates voting rules", &issues))
+	}
+}
+
+fn check_characters(characters: &[CharacterSubmit], rules: &BallotConfig, is_votable: impl Fn(&str) -> bool) -> Vec<ValidationIssue> {
+	let items = characters.iter().map(|c| BallotItem {
+		id: &c.id,
+		reason: c.reason.as_deref(),
+		first: c.first.unwrap_or(false),
+	}).collect::<Vec<_>>();
+	let mut issues = vec![];
+	check_items("characters", &items, rules.max_characters, rules.max_reason_length, is_votable, &mut issues);
+	issues
+}
+
+fn check_musics(musics: &[MusicSubmit], rules: &BallotConfig, is_votable: impl Fn(&str) -> bool) -> Vec<ValidationIssue> {
+	let items = musics.iter().map(|m| BallotItem {
+		id: &m.id,
+		reason: m.reason.as_deref(),
+		first: m.first.unwrap_or(false),
+	}).collect::<Vec<_>>();
+	let mut issues = vec![];
+	check_items("musics", &items, rules.max_musics, rules.max_reason_length, is_votable, &mut issues);
+	issues
+}
+
+fn check_works(works: &[WorkSubmit], rules: &BallotConfig, is_votable: impl Fn(&str) -> bool) -> Vec<ValidationIssue> {
+	let items = works.iter().map(|w| BallotItem {
+		id: &w.id,
+		reason: w.reason.as_deref(),
+		first: false,
+	}).collect::<Vec<_>>();
+	let mut issues = vec![];
+	check_items("works", &items, rules.max_works, rules.max_reason_length, is_votable, &mut issues);
+	issues
+}
+
+/// 检查CP并将成员按ID排序，返回 (转发给 submit-handler 的CP, 错误)
+fn check_cps(cps: &[CPSubmit], rules: &BallotConfig, is_votable: impl Fn(&str) -> bool) -> (Vec<CPSubmit>, Vec<ValidationIssue>) {
+	let mut issues = vec![];
+	let max_cps = rules.max_cps;
+	if cps.len() > max_cps {
+		issues.push(ValidationIssue::new("cps", "TOO_MANY", format!("at most {} CPs can be voted", max_cps)));
+	}
+	let mut seen = HashSet::new();
+	let mut first_seen = false;
+	let mut result = vec![];
+	for (i, cp) in cps.iter().enumerate() {
+		let field = format!("cps[{}]", i);
+		let id_c = cp.id_c.as_deref().filter(|x| !x.is_empty());
+		let members = canonical_cp_members(&cp.id_a, &cp.id_b, id_c);
+		let active = cp.active.clone().filter(|x| !x.is_empty());
+		let mut distinct = members.clone();
+		distinct.dedup();
+		if distinct.len() != members.len() {
+			issues.push(ValidationIssue::new(field.as_str(), "SELF_PAIR", "a CP cannot contain the same character twice"));
+		} else if !seen.insert(members.clone()) {
+			issues.push(ValidationIssue::new(field.as_str(), "DUPLICATE", format!("CP {} is voted more than once", members.join(","))));
+		}
+		if let Some(unknown) = members.iter().find(|m| !is_votable(m)) {
+			issues.push(ValidationIssue::new(field.as_str(), "UNKNOWN_ID", format!("{:?} is not votable", unknown)));
+		}
+		if let Some(active) = &active {
+			if !members.contains(active) {
+				issues.push(ValidationIssue::new(field.as_str(), "INVALID_ACTIVE", format!("{:?} is not a member of this CP", active)));
+			}
+		}
+		if cp.first.unwrap_or(false) {
+			if first_seen {
+				issues.push(ValidationIssue::new(field.as_str(), "MULTIPLE_FIRST", "only one entry can be marked as first"));
+			}
+			first_seen = true;
+		}
+		let mut members = members.into_iter();
+		result.push(CPSubmit {
+			id_a: members.next().unwrap(),
+			id_b: members.next().unwrap(),
+			id_c: members.next(),
+			active: active,
+			first: cp.first,
+		});
+	}
+	(result, issues)
+}
+
+pub fn validate_characters(characters: &[CharacterSubmit]) -> FieldResult<()> {
+	into_result(check_characters(characters, &config().ballot, is_votable_character))
+}
+
+pub fn validate_musics(musics: &[MusicSubmit]) -> FieldResult<()> {
+	into_result(check_musics(musics, &config().ballot, is_votable_music))
+}
+
+pub fn validate_works(works: &[WorkSubmit]) -> FieldResult<()> {
+	into_result(check_works(works, &config().ballot, is_votable_work))
+}
+
+/// 校验CP并将成员按ID排序，返回转发给 submit-handler 的CP
+pub fn canonicalize_cps(cps: &[CPSubmit]) -> FieldResult<Vec<CPSubmit>> {
+	let (result, issues) = check_cps(cps, &config().ballot, is_votable_character);
+	into_result(issues)?;
+	Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rules() -> BallotConfig {
+		BallotConfig {
+			max_characters: 3,
+			max_cps: 2,
+			max_musics: 3,
+			max_works: 2,
+			max_reason_length: 5,
+		}
+	}
+
+	fn votable(id: &str) -> bool {
+		["a", "b", "c", "d", "e"].contains(&id)
+	}
+
+	fn reasons(issues: Vec<ValidationIssue>) -> Vec<(String, &'static str)> {
+		issues.into_iter().map(|i| (i.field, i.reason)).collect()
+	}
+
+	fn issue(field: &str, reason: &'static str) -> Vec<(String, &'static str)> {
+		vec![(field.to_string(), reason)]
+	}
+
+	fn character(id: &str, first: bool) -> CharacterSubmit {
+		CharacterSubmit { id: id.to_string(), reason: None, first: Some(first) }
+	}
+
+	fn music(id: &str, first: bool) -> MusicSubmit {
+		MusicSubmit { id: id.to_string(), reason: None, first: Some(first) }
+	}
+
+	fn work(id: &str) -> WorkSubmit {
+		WorkSubmit { id: id.to_string(), reason: None }
+	}
+
+	fn cp(id_a: &str, id_b: &str, id_c: Option<&str>, active: Option<&str>) -> CPSubmit {
+		CPSubmit {
+			id_a: id_a.to_string(),
+			id_b: id_b.to_string(),
+			id_c: id_c.map(|x| x.to_string()),
+			active: active.map(|x| x.to_string()),
+			first: None,
+		}
+	}
+
+	fn characters(characters: &[CharacterSubmit]) -> Vec<(String, &'static str)> {
+		reasons(check_characters(characters, &rules(), votable))
+	}
+
+	fn cps(cps: &[CPSubmit]) -> Vec<(String, &'static str)> {
+		reasons(check_cps(cps, &rules(), votable).1)
+	}
+
+	#[test]
+	fn accepts_valid_characters() {
+		assert_eq!(characters(&[character("a", true), character("b", false), character("c", false)]), vec![]);
+	}
+
+	#[test]
+	fn rejects_too_many_characters() {
+		let ballot = ["a", "b", "c", "d"].iter().map(|id| character(id, false)).collect::<Vec<_>>();
+		assert_eq!(characters(&ballot), issue("characters", "TOO_MANY"));
+	}
+
+	#[test]
+	fn rejects_duplicate_character() {
+		assert_eq!(characters(&[character("a", false), character("a", false)]), issue("characters[1]", "DUPLICATE"));
+	}
+
+	#[test]
+	fn rejects_unknown_character() {
+		assert_eq!(characters(&[character("a", false), character("x", false)]), issue("characters[1]", "UNKNOWN_ID"));
+	}
+
+	#[test]
+	fn rejects_multiple_first() {
+		assert_eq!(characters(&[character("a", true), character("b", true)]), issue("characters[1]", "MULTIPLE_FIRST"));
+	}
+
+	#[test]
+	fn rejects_long_reason() {
+		let mut long = character("a", false);
+		long.reason = Some("理由太长了啊".to_string());
+		let mut short = character("b", false);
+		short.reason = Some("五个字以内".to_string());
+		assert_eq!(characters(&[long, short]), issue("characters[0]", "REASON_TOO_LONG"));
+	}
+
+	#[test]
+	fn checks_musics() {
+		let musics = ["a", "b", "b", "x"].iter().enumerate().map(|(i, id)| music(id, i < 2)).collect::<Vec<_>>();
+		assert_eq!(reasons(check_musics(&musics, &rules(), votable)), vec![
+			("musics".to_string(), "TOO_MANY"),
+			("musics[1]".to_string(), "MULTIPLE_FIRST"),
+			("musics[2]".to_string(), "DUPLICATE"),
+			("musics[3]".to_string(), "UNKNOWN_ID"),
+		]);
+	}
+
+	#[test]
+	fn checks_works() {
+		assert_eq!(reasons(check_works(&[work("a"), work("b")], &rules(), votable)), vec![]);
+		assert_eq!(reasons(check_works(&[work("a"), work("a"), work("x")], &rules(), votable)), vec![
+			("works".to_string(), "TOO_MANY"),
+			("works[1]".to_string(), "DUPLICATE"),
+			("works[2]".to_string(), "UNKNOWN_ID"),
+		]);
+	}
+
+	#[test]
+	fn canonicalizes_cp_members() {
+		let (result, issues) = check_cps(&[cp("c", "a", Some("b"), Some("c")), cp("b", "a", Some(""), None)], &rules(), votable);
+		assert!(issues.is_empty());
+		assert_eq!((result[0].id_a.as_str(), result[0].id_b.as_str(), result[0].id_c.as_deref()), ("a", "b", Some("c")));
+		assert_eq!((result[1].id_a.as_str(), result[1].id_b.as_str(), result[1].id_c.as_deref()), ("a", "b", None));
+	}
+
+	#[test]
+	fn rejects_too_many_cps() {
+		assert_eq!(cps(&[cp("a", "b", None, None), cp("a", "c", None, None), cp("a", "d", None, None)]), issue("cps", "TOO_MANY"));
+	}
+
+	#[test]
+	fn rejects_cp_self_pair() {
+		assert_eq!(cps(&[cp("a", "a", None, None)]), issue("cps[0]", "SELF_PAIR"));
+		assert_eq!(cps(&[cp("a", "b", Some("a"), None)]), issue("cps[0]", "SELF_PAIR"));
+	}
+
+	#[test]
+	fn rejects_permuted_duplicate_cp() {
+		assert_eq!(cps(&[cp("a", "b", Some("c"), None), cp("c", "a", Some("b"), None)]), issue("cps[1]", "DUPLICATE"));
+	}
+
+	#[test]
+	fn rejects_unknown_cp_member() {
+		assert_eq!(cps(&[cp("a", "x", None, None)]), issue("cps[0]", "UNKNOWN_ID"));
+	}
+
+	#[test]
+	fn rejects_active_outside_cp() {
+		assert_eq!(cps(&[cp("a", "b", None, Some("a"))]), vec![]);
+		assert_eq!(cps(&[cp("a", "b", None, Some("c"))]), issue("cps[0]", "INVALID_ACTIVE"));
+	}
+
+	#[test]
+	fn rejects_multiple_first_cp() {
+		let mut first = cp("a", "b", None, None);
+		first.first = Some(true);
+		let mut second = cp("a", "c", None, None);
+		second.first = Some(true);
+		assert_eq!(cps(&[first, second]), issue("cps[1]", "MULTIPLE_FIRST"));
+	}
+}
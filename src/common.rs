@@ -1,15 +1,67 @@
 
 use std::str::FromStr;
 
+use chrono::{DateTime, Utc};
+
+use juniper::{FieldError, Object, Value, graphql_value};
 use serde::{Deserialize, de::DeserializeOwned};
 use serde_derive::{Serialize};
 use thiserror::Error;
 
+use crate::config::config;
+
 pub static SERVICE_NAME: &'static str = "gateway";
 
-// pub const VOTE_START: DateTime<Utc> = DateTime::from_str("2021-10-01 00:00:00GMT+8").unwrap();
-// pub const VOTE_END: DateTime<Utc> = DateTime::from_str("2021-10-15 00:00:00GMT+8").unwrap();
+/// 投票所在时区（GMT+8），单位分钟
+pub const VOTE_UTC_OFFSET_MINUTES: i32 = 8 * 60;
+
+/// 最早开放的项目的开始时间
+pub fn vote_start() -> DateTime<Utc> {
+	config().schedule().phases().iter().map(|(_, p)| p.open.with_timezone(&Utc)).min().unwrap()
+}
+
+/// 最晚结束的项目的结束时间
+pub fn vote_end() -> DateTime<Utc> {
+	config().schedule().phases().iter().map(|(_, p)| p.close.with_timezone(&Utc)).max().unwrap()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct VoteTokenClaim {
 	pub vote_id: Option<String>
 }
+
+/// 网关自身产生的错误，code 放在 extensions 里供前端区分
+pub fn gateway_error(code: &str, message: &str) -> FieldError {
+	FieldError::new(message, graphql_value!({ "code": code }))
+}
+
+/// 单个校验错误
+#[derive(Debug, Clone)]
+pub struct ValidationIssue {
+	/// 出错的位置，例如问卷问题ID或 characters[2]
+	pub field: String,
+	/// 错误类型，例如 REQUIRED、DUPLICATE
+	pub reason: &'static str,
+	pub message: String,
+}
+
+impl ValidationIssue {
+	pub fn new(field: impl Into<String>, reason: &'static str, message: impl Into<String>) -> ValidationIssue {
+		ValidationIssue { field: field.into(), reason: reason, message: message.into() }
+	}
+}
+
+/// 一次返回所有校验错误，放在 extensions.issues 里供前端逐项标出
+pub fn validation_error(code: &str, message: &str, issues: &[ValidationIssue]) -> FieldError {
+	let issues = issues.iter().map(|issue| {
+		let mut obj = Object::with_capacity(3);
+		obj.add_field("field", Value::scalar(issue.field.clone()));
+		obj.add_field("reason", Value::scalar(issue.reason.to_string()));
+		obj.add_field("message", Value::scalar(issue.message.clone()));
+		Value::object(obj)
+	}).collect::<Vec<_>>();
+	let mut extensions = Object::with_capacity(2);
+	extensions.add_field("code", Value::scalar(code.to_string()));
+	extensions.add_field("issues", Value::list(issues));
+	FieldError::new(message, Value::object(extensions))
+}
@@ -0,0 +1,362 @@
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset};
+use once_cell::sync::OnceCell;
+use serde_derive::{Serialize, Deserialize};
+use thiserror::Error;
+
+static CONFIG: OnceCell<GatewayConfig> = OnceCell::new();
+
+/// 配置文件路径，未设置时尝试读取当前目录下的 gateway.toml
+pub const CONFIG_PATH_ENV: &'static str = "THVOTE_CONFIG";
+pub const DEFAULT_CONFIG_PATH: &'static str = "gateway.toml";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+	#[error("failed to read config file {path}: {source}")]
+	Read { path: String, source: std::io::Error },
+	#[error("failed to parse config file {path}: {source}")]
+	Parse { path: String, source: toml::de::Error },
+	#[error("invalid value for {key}: {reason}")]
+	Invalid { key: String, reason: String },
+}
+
+fn invalid(key: &str, reason: impl Into<String>) -> ConfigError {
+	ConfigError::Invalid { key: key.to_string(), reason: reason.into() }
+}
+
+/// 上游服务地址没有默认值，必须在 [services] 或 THVOTE_* 环境变量中配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServicesConfig {
+	pub user_manager: String,
+	pub submit_handler: String,
+	pub result_query: String,
+	pub submit_validator: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BallotConfig {
+	/// 最多投几个人物
+	pub max_characters: usize,
+	/// 最多投几个CP
+	pub max_cps: usize,
+	/// 最多投几首音乐
+	pub max_musics: usize,
+	/// 最多投几部作品
+	pub max_works: usize,
+	/// 理由最大长度（字符数）
+	pub max_reason_length: usize,
+}
+
+impl Default for BallotConfig {
+	fn default() -> Self {
+		BallotConfig {
+			max_characters: 8,
+			max_cps: 4,
+			max_musics: 12,
+			max_works: 4,
+			max_reason_length: 1024,
+		}
+	}
+}
+
+/// 单个投票项目的开放时间，使用带时区的 RFC 3339 字符串，例如 "2021-10-01T00:00:00+08:00"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VotingPhase {
+	pub open: DateTime<FixedOffset>,
+	pub close: DateTime<FixedOffset>,
+}
+
+/// 没有默认值，缺少任何一个项目的时间都会拒绝启动
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+	pub character: VotingPhase,
+	pub music: VotingPhase,
+	pub cp: VotingPhase,
+	pub paper: VotingPhase,
+	pub work: VotingPhase,
+}
+
+impl ScheduleConfig {
+	pub fn phases(&self) -> [(&'static str, &VotingPhase); 5] {
+		[
+			("character", &self.character),
+			("music", &self.music),
+			("cp", &self.cp),
+			("paper", &self.paper),
+			("work", &self.work),
+		]
+	}
+}
+
+/// 可以放入 additional_fingerprint 的信号
+pub const FINGERPRINT_SIGNALS: [&'static str; 4] = ["client", "user_agent", "accept_language", "connection"];
+
+/// 附加在用户操作和投票提交上的指纹，供反作弊使用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FingerprintConfig {
+	/// 客户端提供的设备ID或浏览器指纹所在的请求头
+	pub client_header: String,
+	/// 包含的信号，见 FINGERPRINT_SIGNALS
+	pub signals: Vec<String>,
+	/// 单个信号的最大长度，超出部分截断
+	pub max_signal_length: usize,
+}
+
+impl Default for FingerprintConfig {
+	fn default() -> Self {
+		FingerprintConfig {
+			client_header: "X-Device-Fingerprint".to_string(),
+			signals: FINGERPRINT_SIGNALS.iter().map(|s| s.to_string()).collect(),
+			max_signal_length: 512,
+		}
+	}
+}
+
+/// 登录后把token写入 HttpOnly cookie 的设置，默认关闭
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionCookieConfig {
+	pub enabled: bool,
+	/// 只在 HTTPS 下发送，本地调试时可关闭
+	pub secure: bool,
+	/// Strict、Lax 或 None，None 要求 secure
+	pub same_site: String,
+	pub domain: Option<String>,
+	/// 为 true 时 cookie 随 token 的 exp 过期，为 false 时浏览器关闭后失效
+	pub persistent: bool,
+}
+
+impl Default for SessionCookieConfig {
+	fn default() -> Self {
+		SessionCookieConfig {
+			enabled: false,
+			secure: true,
+			same_site: "Strict".to_string(),
+			domain: None,
+			persistent: true,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GatewayConfig {
+	/// 监听地址
+	pub bind: String,
+	/// 验证投票token的公钥（JWKS），按 kid 选择
+	pub jwks_path: String,
+	/// 重新读取 JWKS 文件的间隔（秒）
+	pub jwks_reload_secs: u64,
+	/// 允许的跨域来源，为空时允许任意来源但不允许携带 cookie
+	pub cors_origins: Vec<String>,
+	/// 请求上游服务的超时（毫秒）
+	pub upstream_timeout_ms: u64,
+	/// 问卷定义文件，未设置时不校验问卷内容
+	pub questionnaire_path: Option<String>,
+	/// 投票数据文件，修改后或收到 SIGHUP 时自动重新加载
+	pub catalog_path: String,
+	/// 上游服务地址
+	pub services: ServicesConfig,
+	/// 投票规则
+	pub ballot: BallotConfig,
+	/// 各项目的投票时间，必须配置
+	pub schedule: Option<ScheduleConfig>,
+	/// 登录token的 cookie
+	pub session_cookie: SessionCookieConfig,
+	/// 反作弊指纹
+	pub fingerprint: FingerprintConfig,
+}
+
+impl Default for GatewayConfig {
+	fn default() -> Self {
+		GatewayConfig {
+			bind: "0.0.0.0:80".to_string(),
+			jwks_path: "../keys/jwks.json".to_string(),
+			jwks_reload_secs: 60,
+			cors_origins: vec![],
+			upstream_timeout_ms: 10000,
+			questionnaire_path: None,
+			catalog_path: "catalog.json".to_string(),
+			services: ServicesConfig::default(),
+			ballot: BallotConfig::default(),
+			schedule: None,
+			session_cookie: SessionCookieConfig::default(),
+			fingerprint: FingerprintConfig::default(),
+		}
+	}
+}
+
+impl GatewayConfig {
+	/// 读取配置文件，再使用 THVOTE_* 环境变量覆盖，最后校验
+	pub fn load() -> Result<GatewayConfig, ConfigError> {
+		let (path, required) = match std::env::var(CONFIG_PATH_ENV) {
+			Ok(path) => (path, true),
+			Err(_) => (DEFAULT_CONFIG_PATH.to_string(), false),
+		};
+		let mut config = if required || Path::new(&path).exists() {
+			Self::from_file(&path)?
+		} else {
+			GatewayConfig::default()
+		};
+		config.apply_env()?;
+		config.validate()?;
+		Ok(config)
+	}
+
+	pub fn from_file(path: &str) -> Result<GatewayConfig, ConfigError> {
+		let content = std::fs::read_to_string(path).map_err(|e| ConfigError::Read { path: path.to_string(), source: e })?;
+		toml::from_str(&content).map_err(|e| ConfigError::Parse { path: path.to_string(), source: e })
+	}
+
+	fn apply_env(&mut self) -> Result<(), ConfigError> {
+		let env = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+		if let Some(v) = env("THVOTE_BIND") {
+			self.bind = v;
+		}
+		if let Some(v) = env("THVOTE_JWKS_PATH") {
+			self.jwks_path = v;
+		}
+		if let Some(v) = env("THVOTE_JWKS_RELOAD_SECS") {
+			self.jwks_reload_secs = v.parse().map_err(|_| invalid("THVOTE_JWKS_RELOAD_SECS", format!("{:?} is not a number", v)))?;
+		}
+		if let Some(v) = env("THVOTE_CORS_ORIGINS") {
+			self.cors_origins = v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+		}
+		if let Some(v) = env("THVOTE_UPSTREAM_TIMEOUT_MS") {
+			self.upstream_timeout_ms = v.parse().map_err(|_| invalid("THVOTE_UPSTREAM_TIMEOUT_MS", format!("{:?} is not a number", v)))?;
+		}
+		if let Some(v) = env("THVOTE_QUESTIONNAIRE_PATH") {
+			self.questionnaire_path = Some(v);
+		}
+		if let Some(v) = env("THVOTE_CATALOG_PATH") {
+			self.catalog_path = v;
+		}
+		if let Some(v) = env("THVOTE_SESSION_COOKIES") {
+			self.session_cookie.enabled = v.parse().map_err(|_| invalid("THVOTE_SESSION_COOKIES", format!("{:?} is not true or false", v)))?;
+		}
+		if let Some(v) = env("THVOTE_FINGERPRINT_SIGNALS") {
+			self.fingerprint.signals = v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+		}
+		if let Some(v) = env("THVOTE_USER_MANAGER") {
+			self.services.user_manager = v;
+		}
+		if let Some(v) = env("THVOTE_SUBMIT_HANDLER") {
+			self.services.submit_handler = v;
+		}
+		if let Some(v) = env("THVOTE_RESULT_QUERY") {
+			self.services.result_query = v;
+		}
+		if let Some(v) = env("THVOTE_SUBMIT_VALIDATOR") {
+			self.services.submit_validator = v;
+		}
+		Ok(())
+	}
+
+	pub fn validate(&self) -> Result<(), ConfigError> {
+		self.bind.parse::<SocketAddr>().map_err(|_| invalid("bind", format!("{:?} is not a socket address", self.bind)))?;
+		if !Path::new(&self.jwks_path).is_file() {
+			return Err(invalid("jwks_path", format!("{:?} does not exist", self.jwks_path)));
+		}
+		if self.jwks_reload_secs == 0 {
+			return Err(invalid("jwks_reload_secs", "must be greater than 0"));
+		}
+		if let Some(path) = &self.questionnaire_path {
+			if !Path::new(path).is_file() {
+				return Err(invalid("questionnaire_path", format!("{:?} does not exist", path)));
+			}
+		}
+		if !Path::new(&self.catalog_path).is_file() {
+			return Err(invalid("catalog_path", format!("{:?} does not exist", self.catalog_path)));
+		}
+		if self.upstream_timeout_ms == 0 {
+			return Err(invalid("upstream_timeout_ms", "must be greater than 0"));
+		}
+		for origin in self.cors_origins.iter() {
+			if !is_http_url(origin) {
+				return Err(invalid("cors_origins", format!("{:?} is not an http(s) origin", origin)));
+			}
+		}
+		if self.ballot.max_characters == 0 {
+			return Err(invalid("ballot.max_characters", "must be greater than 0"));
+		}
+		if self.ballot.max_cps == 0 {
+			return Err(invalid("ballot.max_cps", "must be greater than 0"));
+		}
+		if self.ballot.max_musics == 0 {
+			return Err(invalid("ballot.max_musics", "must be greater than 0"));
+		}
+		if self.ballot.max_works == 0 {
+			return Err(invalid("ballot.max_works", "must be greater than 0"));
+		}
+		let schedule = self.schedule.as_ref().ok_or_else(|| invalid("schedule", "[schedule.character], [schedule.music], [schedule.cp], [schedule.paper] and [schedule.work] must be configured"))?;
+		for (name, phase) in schedule.phases().iter() {
+			if phase.open >= phase.close {
+				return Err(invalid(&format!("schedule.{}", name), "open must be earlier than close"));
+			}
+		}
+		if self.session_cookie.enabled && self.cors_origins.is_empty() {
+			return Err(invalid("session_cookie.enabled", "requires an explicit cors_origins allowlist"));
+		}
+		match self.session_cookie.same_site.as_str() {
+			"Strict" | "Lax" => {},
+			"None" if self.session_cookie.secure => {},
+			"None" => return Err(invalid("session_cookie.same_site", "None requires secure = true")),
+			other => return Err(invalid("session_cookie.same_site", format!("{:?} is not Strict, Lax or None", other))),
+		}
+		if let Some(signal) = self.fingerprint.signals.iter().find(|s| !FINGERPRINT_SIGNALS.contains(&s.as_str())) {
+			return Err(invalid("fingerprint.signals", format!("unknown signal {:?}, expected one of {:?}", signal, FINGERPRINT_SIGNALS)));
+		}
+		if self.fingerprint.client_header.trim().is_empty() {
+			return Err(invalid("fingerprint.client_header", "must not be empty"));
+		}
+		if self.fingerprint.max_signal_length == 0 {
+			return Err(invalid("fingerprint.max_signal_length", "must be greater than 0"));
+		}
+		let services = [
+			("services.user_manager", "THVOTE_USER_MANAGER", &self.services.user_manager),
+			("services.submit_handler", "THVOTE_SUBMIT_HANDLER", &self.services.submit_handler),
+			("services.result_query", "THVOTE_RESULT_QUERY", &self.services.result_query),
+			("services.submit_validator", "THVOTE_SUBMIT_VALIDATOR", &self.services.submit_validator),
+		];
+		for (key, env, url) in services.iter() {
+			if url.is_empty() {
+				return Err(invalid(key, format!("must be set in [services] or with {}", env)));
+			}
+			if !is_http_url(url) {
+				return Err(invalid(key, format!("{:?} is not an http(s) base URL", url)));
+			}
+		}
+		Ok(())
+	}
+
+	/// validate 保证已经配置
+	pub fn schedule(&self) -> &ScheduleConfig {
+		self.schedule.as_ref().expect("schedule not configured")
+	}
+
+	pub fn upstream_timeout(&self) -> Duration {
+		Duration::from_millis(self.upstream_timeout_ms)
+	}
+}
+
+fn is_http_url(s: &str) -> bool {
+	let rest = s.strip_prefix("http://").or_else(|| s.strip_prefix("https://"));
+	matches!(rest, Some(host) if !host.is_empty())
+}
+
+pub fn init(config: GatewayConfig) {
+	CONFIG.set(config).expect("config already initialized");
+}
+
+pub fn config() -> &'static GatewayConfig {
+	CONFIG.get().expect("config not initialized")
+}
@@ -1,11 +1,62 @@
-use jwt_simple::prelude::{ES256kPublicKey, ES256kKeyPair};
+use std::sync::{Arc, Mutex};
+
+use actix_web::cookie::Cookie;
+use juniper::FieldResult;
+use once_cell::sync::OnceCell;
+
+use crate::auth::{TokenError, VoterIdentity, verify_vote_token};
+use crate::i18n::Language;
+use crate::jwks::KeySet;
 
 
 #[derive(Debug, Clone)]
 pub struct Context {
     pub user_ip: String,
     pub additional_fingureprint: Option<String>,
-    pub public_key: ES256kKeyPair
+    /// 请求开始时的验证公钥
+    pub keys: Arc<KeySet>,
+    /// 根据 Accept-Language 协商的显示语言，字段的 lang 参数优先
+    pub lang: Language,
+    /// audience 为投票的 Bearer token 或 vote_token cookie 中的投票token
+    pub vote_token: Option<String>,
+    /// 其他 Bearer token 或 session_token cookie 中的登录token
+    pub session_token: Option<String>,
+    /// 本次请求中投票token的验证结果，只验证一次
+    pub voter: OnceCell<(String, Result<VoterIdentity, TokenError>)>,
+    /// 需要写入响应的 cookie
+    pub response_cookies: Arc<Mutex<Vec<Cookie<'static>>>>
+}
+
+impl Context {
+    /// 要求请求带有有效的投票token，参数中的token优先（空参数视为未提供），失败时按原因返回 TOKEN_* 错误
+    pub fn require_voter(&self, vote_token: Option<&str>) -> FieldResult<VoterIdentity> {
+        let vote_token = vote_token.filter(|t| !t.is_empty()).or(self.vote_token.as_deref()).unwrap_or("");
+        self.verify_voter(vote_token).map_err(TokenError::into_field_error)
+    }
+
+    /// 登录token由用户服务校验，这里只取出参数或请求中携带的token
+    pub fn require_session_token(&self, user_token: Option<String>) -> FieldResult<String> {
+        user_token.filter(|t| !t.is_empty()).or_else(|| self.session_token.clone())
+            .filter(|t| !t.is_empty())
+            .ok_or_else(|| TokenError::SessionMissing.into_field_error())
+    }
+
+    pub fn set_cookie(&self, cookie: Cookie<'static>) {
+        self.response_cookies.lock().unwrap().push(cookie);
+    }
+
+    fn verify_voter(&self, vote_token: &str) -> Result<VoterIdentity, TokenError> {
+        if vote_token.is_empty() {
+            return Err(TokenError::Missing);
+        }
+        let (token, result) = self.voter.get_or_init(|| (vote_token.to_string(), verify_vote_token(&self.keys, vote_token)));
+        if token == vote_token {
+            result.clone()
+        } else {
+            // 同一请求中使用了不同的token
+            verify_vote_token(&self.keys, vote_token)
+        }
+    }
 }
 
 impl juniper::Context for Context {}
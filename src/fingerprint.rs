@@ -0,0 +1,41 @@
+
+use actix_web::{HttpRequest, http};
+use serde_json::{Map, Value};
+
+use crate::config::config;
+
+fn header(req: &HttpRequest, name: &str) -> Option<String> {
+	req.headers().get(name).and_then(|v| v.to_str().ok()).map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn truncate(mut s: String, max_length: usize) -> String {
+	if let Some((i, _)) = s.char_indices().nth(max_length) {
+		s.truncate(i);
+	}
+	s
+}
+
+/// 按配置收集请求中的指纹信号，序列化为 JSON 对象；没有任何信号时返回 None
+pub fn request_fingerprint(req: &HttpRequest) -> Option<String> {
+	let settings = &config().fingerprint;
+	let mut signals = Map::new();
+	for signal in settings.signals.iter() {
+		let value = match signal.as_str() {
+			// 客户端提供的设备ID或浏览器指纹，由反作弊服务校验签名
+			"client" => header(req, &settings.client_header),
+			"user_agent" => header(req, http::header::USER_AGENT.as_str()),
+			"accept_language" => header(req, http::header::ACCEPT_LANGUAGE.as_str()),
+			// 协议和 HTTP 版本，经过反向代理时取转发头中的协议
+			"connection" => Some(format!("{}/{:?}", req.connection_info().scheme(), req.version())),
+			_ => None,
+		};
+		if let Some(value) = value {
+			signals.insert(signal.clone(), Value::String(truncate(value, settings.max_signal_length)));
+		}
+	}
+	if signals.is_empty() {
+		None
+	} else {
+		Some(Value::Object(signals).to_string())
+	}
+}
@@ -0,0 +1,140 @@
+
+use serde_derive::{Serialize, Deserialize};
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(description="显示语言")]
+pub enum Language {
+	/// 简体中文
+	#[serde(rename = "zh-Hans")]
+	ZhHans,
+	/// 繁体中文
+	#[serde(rename = "zh-Hant")]
+	ZhHant,
+	/// 日语
+	#[serde(rename = "ja")]
+	Ja,
+	/// 英语
+	#[serde(rename = "en")]
+	En,
+}
+
+impl Default for Language {
+	fn default() -> Self {
+		Language::ZhHans
+	}
+}
+
+impl Language {
+	/// 识别 BCP 47 语言标签，例如 zh-TW、zh-Hant-HK、ja-JP、en-US
+	pub fn from_tag(tag: &str) -> Option<Language> {
+		let tag = tag.trim().to_ascii_lowercase();
+		let mut parts = tag.split(|c| c == '-' || c == '_');
+		match parts.next()? {
+			"zh" => {
+				// 明确写出的文字优先，否则按地区判断
+				let subtags: Vec<&str> = parts.collect();
+				let traditional = if subtags.contains(&"hant") {
+					true
+				} else if subtags.contains(&"hans") {
+					false
+				} else {
+					subtags.iter().any(|p| *p == "tw" || *p == "hk" || *p == "mo")
+				};
+				Some(if traditional { Language::ZhHant } else { Language::ZhHans })
+			},
+			"ja" => Some(Language::Ja),
+			"en" => Some(Language::En),
+			_ => None
+		}
+	}
+
+	/// 根据 Accept-Language 选择权重最高的支持语言
+	pub fn negotiate(accept_language: &str) -> Option<Language> {
+		let mut candidates: Vec<(f32, Language)> = accept_language.split(',').filter_map(|item| {
+			let mut parts = item.split(';');
+			let lang = Language::from_tag(parts.next()?)?;
+			let q = parts.find_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.parse::<f32>().ok())).unwrap_or(1.0);
+			if q > 0.0 { Some((q, lang)) } else { None }
+		}).collect();
+		// 权重相同时保持原来的顺序
+		candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+		candidates.first().map(|(_, lang)| *lang)
+	}
+}
+
+#[derive(juniper::GraphQLObject, Clone, Debug, Default, Serialize, Deserialize)]
+#[graphql(description="多语言名称")]
+pub struct LocalizedName {
+	/// 简体中文
+	#[serde(rename = "zh-Hans")]
+	pub zh_hans: Option<String>,
+	/// 繁体中文
+	#[serde(rename = "zh-Hant")]
+	pub zh_hant: Option<String>,
+	/// 日语
+	pub ja: Option<String>,
+	/// 英语
+	pub en: Option<String>,
+}
+
+impl LocalizedName {
+	pub fn get(&self, lang: Language) -> Option<&str> {
+		match lang {
+			Language::ZhHans => self.zh_hans.as_deref(),
+			Language::ZhHant => self.zh_hant.as_deref(),
+			Language::Ja => self.ja.as_deref(),
+			Language::En => self.en.as_deref(),
+		}
+	}
+
+	/// 缺少该语言时依次回退到简体中文和默认名称
+	pub fn resolve<'a>(&'a self, lang: Language, default: &'a str) -> &'a str {
+		self.get(lang).or_else(|| self.get(Language::ZhHans)).unwrap_or(default)
+	}
+
+	pub fn all(&self) -> impl Iterator<Item = &String> {
+		self.zh_hans.iter().chain(self.zh_hant.iter()).chain(self.ja.iter()).chain(self.en.iter())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_tags() {
+		assert_eq!(Language::from_tag("zh"), Some(Language::ZhHans));
+		assert_eq!(Language::from_tag("zh-CN"), Some(Language::ZhHans));
+		assert_eq!(Language::from_tag("zh-TW"), Some(Language::ZhHant));
+		assert_eq!(Language::from_tag("zh_hk"), Some(Language::ZhHant));
+		assert_eq!(Language::from_tag("zh-Hant"), Some(Language::ZhHant));
+		assert_eq!(Language::from_tag(" ja-JP "), Some(Language::Ja));
+		assert_eq!(Language::from_tag("en-US"), Some(Language::En));
+		assert_eq!(Language::from_tag("fr"), None);
+		assert_eq!(Language::from_tag("*"), None);
+	}
+
+	#[test]
+	fn script_takes_precedence_over_region() {
+		assert_eq!(Language::from_tag("zh-Hans-HK"), Some(Language::ZhHans));
+		assert_eq!(Language::from_tag("zh-Hans-TW"), Some(Language::ZhHans));
+		assert_eq!(Language::from_tag("zh-Hant-CN"), Some(Language::ZhHant));
+	}
+
+	#[test]
+	fn negotiates_by_weight() {
+		assert_eq!(Language::negotiate("en;q=0.5, ja;q=0.8, zh-TW;q=0.3"), Some(Language::Ja));
+		assert_eq!(Language::negotiate("fr, en;q=0.1"), Some(Language::En));
+		assert_eq!(Language::negotiate("ja;q=0, en;q=0.2"), Some(Language::En));
+		assert_eq!(Language::negotiate("fr, de;q=0.9"), None);
+		assert_eq!(Language::negotiate(""), None);
+	}
+
+	#[test]
+	fn keeps_order_for_equal_weights() {
+		assert_eq!(Language::negotiate("en, ja"), Some(Language::En));
+		assert_eq!(Language::negotiate("ja;q=0.7, zh-Hant;q=0.7, en;q=0.5"), Some(Language::Ja));
+		// 无法解析的权重按 1 处理
+		assert_eq!(Language::negotiate("en;q=0.9, ja;q=abc"), Some(Language::Ja));
+	}
+}
@@ -0,0 +1,108 @@
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use jwt_simple::prelude::ES256kPublicKey;
+use jwt_simple::reexports::ct_codecs::{Base64UrlSafeNoPadding, Decoder};
+use once_cell::sync::OnceCell;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::config::config;
+
+static KEYS: OnceCell<RwLock<Arc<KeySet>>> = OnceCell::new();
+
+/// 单个 secp256k1 公钥（RFC 7517），只保留验证所需的字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+	pub kty: String,
+	pub crv: String,
+	pub kid: String,
+	pub x: String,
+	pub y: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub alg: Option<String>,
+	#[serde(rename = "use", default, skip_serializing_if = "Option::is_none")]
+	pub key_use: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwkSet {
+	pub keys: Vec<Jwk>,
+}
+
+/// 按 kid 索引的验证公钥
+#[derive(Debug)]
+pub struct KeySet {
+	keys: HashMap<String, ES256kPublicKey>,
+	/// 对外公开的 JWKS，不含私钥字段
+	pub document: JwkSet,
+}
+
+fn decode_coordinate(kid: &str, name: &str, value: &str) -> Result<Vec<u8>, String> {
+	let bytes = Base64UrlSafeNoPadding::decode_to_vec(value, None).map_err(|_| format!("key {:?} has an invalid {}", kid, name))?;
+	if bytes.len() != 32 {
+		return Err(format!("key {:?} has an invalid {}", kid, name));
+	}
+	Ok(bytes)
+}
+
+impl KeySet {
+	pub fn from_file(path: &str) -> Result<KeySet, String> {
+		let content = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+		let document: JwkSet = serde_json::from_str(&content).map_err(|e| format!("failed to parse {}: {}", path, e))?;
+		let mut keys = HashMap::new();
+		for jwk in document.keys.iter() {
+			if jwk.kty != "EC" || jwk.crv != "secp256k1" {
+				return Err(format!("key {:?} is not a secp256k1 EC key", jwk.kid));
+			}
+			// 未压缩的 SEC1 编码：0x04 || x || y
+			let mut point = vec![0x04u8];
+			point.extend(decode_coordinate(&jwk.kid, "x", &jwk.x)?);
+			point.extend(decode_coordinate(&jwk.kid, "y", &jwk.y)?);
+			let key = ES256kPublicKey::from_bytes(&point).map_err(|e| format!("key {:?} is invalid: {}", jwk.kid, e))?;
+			if keys.insert(jwk.kid.clone(), key.with_key_id(&jwk.kid)).is_some() {
+				return Err(format!("duplicate key id {:?}", jwk.kid));
+			}
+		}
+		if keys.is_empty() {
+			return Err(format!("{} contains no keys", path));
+		}
+		Ok(KeySet { keys: keys, document: document })
+	}
+
+	/// 按 token 头部的 kid 选择公钥，只有一个公钥时允许省略 kid
+	pub fn select(&self, kid: Option<&str>) -> Option<&ES256kPublicKey> {
+		match kid {
+			Some(kid) => self.keys.get(kid),
+			None if self.keys.len() == 1 => self.keys.values().next(),
+			None => None,
+		}
+	}
+}
+
+pub fn init(keys: KeySet) {
+	if KEYS.set(RwLock::new(Arc::new(keys))).is_err() {
+		panic!("keys already initialized");
+	}
+}
+
+/// 当前的公钥集合，重新加载后旧的快照仍然有效
+pub fn keys() -> Arc<KeySet> {
+	KEYS.get().expect("keys not initialized").read().unwrap().clone()
+}
+
+/// 定期重新读取 JWKS 文件，失败时保留原来的公钥
+pub async fn watch_keys() {
+	let path = config().jwks_path.clone();
+	let mut interval = tokio::time::interval(Duration::from_secs(config().jwks_reload_secs));
+	// 第一次 tick 立即返回，启动时已经加载过
+	interval.tick().await;
+	loop {
+		interval.tick().await;
+		match KeySet::from_file(&path) {
+			Ok(keys) => *KEYS.get().expect("keys not initialized").write().unwrap() = Arc::new(keys),
+			Err(e) => log::warn!("failed to reload keys, keeping previous keys: {}", e),
+		}
+	}
+}
@@ -1,8 +1,8 @@
 #![allow(non_snake_case)]
 extern crate juniper;
 
-use std::io::{self, Read};
-use std::sync::Arc;
+use std::io;
+use std::sync::{Arc, Mutex};
 
 
 use actix_cors::Cors;
@@ -13,34 +13,32 @@ use context::Context;
 use juniper_actix::{
 	graphiql_handler as gqli_handler, graphql_handler, playground_handler as play_handler,
 };
-use jwt_simple::prelude::{ES256kKeyPair, ES256kPublicKey};
 use once_cell::sync::OnceCell;
 use submit_handler::{getVotingStatus_impl, getSubmitPaperVote_impl};
 
 #[macro_use]
 mod common;
+mod auth;
+mod ballot;
+mod config;
+mod questionnaire;
+mod schedule;
 mod schema;
 mod services;
 mod context;
+mod fingerprint;
+mod i18n;
+mod jwks;
 
 pub mod user_manager;
 pub mod result_query;
 pub mod submit_handler;
 pub mod vote_data;
 
+use crate::config::{GatewayConfig, config};
+use crate::i18n::Language;
 use crate::schema::{create_schema, Schema};
 
-static KEY: OnceCell<ES256kKeyPair> = OnceCell::new();
-
-fn read_a_file(filename: &str) -> std::io::Result<Vec<u8>> {
-	let mut file = std::fs::File::open(filename)?;
-
-	let mut data = Vec::new();
-	file.read_to_end(&mut data)?;
-
-	return Ok(data);
-}
-
 async fn graphiql_handler() -> Result<HttpResponse, Error> {
 	gqli_handler("/graphql", None).await
 }
@@ -52,35 +50,48 @@ async fn graphql(
 	payload: actix_web::web::Payload,
 	schema: web::Data<Schema>,
 ) -> Result<HttpResponse, Error> {
-	//let vote_token = req.cookie("vote_token").map(|f| f.value().to_string());
+	let (vote_token, session_token) = auth::request_tokens(&req);
 	let ctx = Context {
-		//vote_token: vote_token,
-		additional_fingureprint: None,
-		// TODO: additional fingerprint
+		vote_token: vote_token,
+		session_token: session_token,
+		additional_fingureprint: fingerprint::request_fingerprint(&req),
 		user_ip: req.connection_info().realip_remote_addr().unwrap_or("unknown").to_string(),
-		public_key: KEY.get().unwrap().clone()
+		keys: jwks::keys(),
+		lang: req.headers().get(http::header::ACCEPT_LANGUAGE)
+			.and_then(|v| v.to_str().ok())
+			.and_then(Language::negotiate)
+			.unwrap_or_default(),
+		voter: OnceCell::new(),
+		response_cookies: Arc::new(Mutex::new(vec![]))
 	};
-	graphql_handler(&schema, &ctx, req, payload).await
+	let mut response = graphql_handler(&schema, &ctx, req, payload).await?;
+	for cookie in ctx.response_cookies.lock().unwrap().iter() {
+		response.add_cookie(cookie)?;
+	}
+	Ok(response)
 }
 
 
-async fn user_token_status(body: actix_web::web::Json<user_manager::TokenStatusInputs>) -> Result<web::Json<user_manager::TokenStatusOutput>, Error> {
+async fn user_token_status(req: actix_web::HttpRequest, body: actix_web::web::Json<user_manager::TokenStatusInputs>) -> Result<web::Json<user_manager::TokenStatusOutput>, Error> {
 	let result = user_manager::user_token_status(body.user_token.clone(), body.vote_token.clone()).await;
 	if result.is_ok() {
 		let mut ret = user_manager::TokenStatusOutput { status: "valid".to_string(), voting_status: None, papers_json: None };
 		let ctx = Context {
-			//vote_token: vote_token,
-			additional_fingureprint: None,
-			// TODO: additional fingerprint
-			user_ip: "".to_string(),
-			public_key: KEY.get().unwrap().clone()
+			vote_token: None,
+			session_token: None,
+			additional_fingureprint: fingerprint::request_fingerprint(&req),
+			user_ip: req.connection_info().realip_remote_addr().unwrap_or("unknown").to_string(),
+			keys: jwks::keys(),
+			lang: Language::default(),
+			voter: OnceCell::new(),
+			response_cookies: Arc::new(Mutex::new(vec![]))
 		};
 		if let Some(vote_token) = &body.vote_token {
-			let ret2 = getVotingStatus_impl(&ctx, vote_token.clone()).await;
+			let ret2 = getVotingStatus_impl(&ctx, Some(vote_token.clone())).await;
 			if let Ok(voting_status) = ret2 {
 				ret.voting_status = Some(voting_status.clone());
 				if voting_status.papers {
-					ret.papers_json = Some(getSubmitPaperVote_impl(&ctx, vote_token.clone()).await.unwrap().papers_json);
+					ret.papers_json = Some(getSubmitPaperVote_impl(&ctx, Some(vote_token.clone())).await.unwrap().papers_json);
 				}
 			}
 		}
@@ -91,6 +102,11 @@ async fn user_token_status(body: actix_web::web::Json<user_manager::TokenStatusI
 }
 
 
+/// 供其他服务验证投票token的公钥
+async fn jwks_handler() -> Result<web::Json<jwks::JwkSet>, Error> {
+	Ok(web::Json(jwks::keys().document.clone()))
+}
+
 async fn server_time() -> Result<String, Error> {
 	let now = Utc::now().to_rfc3339();
 	Ok(now.into())
@@ -98,22 +114,63 @@ async fn server_time() -> Result<String, Error> {
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
-	std::env::set_var("RUST_LOG", "actix_web=info");
+	std::env::set_var("RUST_LOG", "actix_web=info,thvote_gateway=info");
 	env_logger::init();
 
-	let key = ES256kKeyPair::from_pem(std::str::from_utf8(&read_a_file("../keys/key-priv.pem").unwrap()).unwrap()).unwrap();
-	KEY.set(key).unwrap();
+	let gateway_config = match GatewayConfig::load() {
+		Ok(c) => c,
+		Err(e) => {
+			log::error!("invalid gateway configuration: {}", e);
+			std::process::exit(1);
+		}
+	};
+	config::init(gateway_config);
+
+	if let Some(path) = &config().questionnaire_path {
+		match questionnaire::Questionnaire::from_file(path) {
+			Ok(q) => questionnaire::init(q),
+			Err(e) => {
+				log::error!("invalid questionnaire: {}", e);
+				std::process::exit(1);
+			}
+		}
+	}
+
+	match vote_data::Catalog::from_file(&config().catalog_path) {
+		Ok(c) => vote_data::init(c),
+		Err(e) => {
+			log::error!("invalid catalog: {}", e);
+			std::process::exit(1);
+		}
+	}
+	actix_web::rt::spawn(vote_data::watch_catalog());
+
+	match jwks::KeySet::from_file(&config().jwks_path) {
+		Ok(k) => jwks::init(k),
+		Err(e) => {
+			log::error!("invalid jwks: {}", e);
+			std::process::exit(1);
+		}
+	}
+	actix_web::rt::spawn(jwks::watch_keys());
 
 	// Start http server
 	HttpServer::new(move || {
+		let mut cors = Cors::default()
+			.allow_any_header()
+			.allow_any_method();
+		if config().cors_origins.is_empty() {
+			// 任意来源时不允许携带 cookie，否则任何网站都能以用户身份调用接口
+			cors = cors.allow_any_origin();
+		} else {
+			for origin in config().cors_origins.iter() {
+				cors = cors.allowed_origin(origin);
+			}
+			cors = cors.supports_credentials();
+		}
 		App::new()
 			.app_data(web::Data::new(create_schema()))
-			.wrap(
-				Cors::default()
-				.allow_any_origin()
-				.allow_any_header()
-				.allow_any_method()
-			)
+			.wrap(cors)
 			// .wrap(middleware::Compress::default())
 			// .wrap(middleware::Logger::default())
 			.service(
@@ -125,8 +182,9 @@ async fn main() -> io::Result<()> {
 			.service(web::resource("/graphiql").route(web::get().to(graphiql_handler)))
 			.service(web::resource("/user-token-status").route(web::post().to(user_token_status)))
 			.service(web::resource("/server-time").route(web::get().to(server_time)))
+			.service(web::resource("/.well-known/jwks.json").route(web::get().to(jwks_handler)))
 	})
-	.bind("0.0.0.0:80")?
+	.bind(&config().bind)?
 	.run()
 	.await
-}
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,360 @@
+
+use std::collections::{HashMap, HashSet};
+
+use juniper::FieldResult;
+use once_cell::sync::OnceCell;
+use serde_derive::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::common::{ValidationIssue, gateway_error, validation_error};
+use crate::context::Context;
+
+static QUESTIONNAIRE: OnceCell<Questionnaire> = OnceCell::new();
+
+/// 文本回答默认的最大长度
+const DEFAULT_TEXT_MAX_LENGTH: i32 = 1000;
+
+// ------------------------------------------------
+// Questionnaire definition
+// ------------------------------------------------
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[graphql(description="问题类型")]
+pub enum QuestionKind {
+	/// 单选
+	SingleChoice,
+	/// 多选
+	MultiChoice,
+	/// 文本
+	Text,
+	/// 数值
+	Number,
+}
+
+#[derive(juniper::GraphQLObject, Clone, Debug, Serialize, Deserialize)]
+#[graphql(description="问题选项")]
+pub struct QuestionOption {
+	/// 选项ID
+	pub id: String,
+	/// 选项文字
+	pub label: String,
+}
+
+#[derive(juniper::GraphQLObject, Clone, Debug, Serialize, Deserialize)]
+#[graphql(description="问题显示条件：question 的回答包含 any_of 中任一选项时显示")]
+pub struct QuestionCondition {
+	/// 依赖的问题ID
+	pub question: String,
+	/// 满足任一选项即显示
+	pub any_of: Vec<String>,
+}
+
+#[derive(juniper::GraphQLObject, Clone, Debug, Serialize, Deserialize)]
+#[graphql(description="问卷问题")]
+pub struct Question {
+	/// 问题ID
+	pub id: String,
+	/// 问题
+	pub title: String,
+	/// 类型
+	pub kind: QuestionKind,
+	/// 选项（单选/多选）
+	#[serde(default)]
+	pub options: Vec<QuestionOption>,
+	/// 是否必填
+	#[serde(default)]
+	pub required: bool,
+	/// 最少选择数（多选）
+	pub min_choices: Option<i32>,
+	/// 最多选择数（多选）
+	pub max_choices: Option<i32>,
+	/// 最小值（数值）
+	pub min_value: Option<f64>,
+	/// 最大值（数值）
+	pub max_value: Option<f64>,
+	/// 最大长度（文本）
+	pub max_length: Option<i32>,
+	/// 显示条件，为空时总是显示
+	pub visible_if: Option<QuestionCondition>,
+}
+
+#[derive(juniper::GraphQLObject, Clone, Debug, Serialize, Deserialize)]
+#[graphql(description="问卷")]
+pub struct Questionnaire {
+	/// 问卷版本
+	pub version: String,
+	/// 所有问题
+	pub questions: Vec<Question>,
+}
+
+impl Questionnaire {
+	pub fn from_file(path: &str) -> Result<Questionnaire, String> {
+		let content = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+		let questionnaire: Questionnaire = serde_json::from_str(&content).map_err(|e| format!("failed to parse {}: {}", path, e))?;
+		questionnaire.check()?;
+		Ok(questionnaire)
+	}
+
+	/// 检查问卷定义本身是否一致
+	fn check(&self) -> Result<(), String> {
+		let mut seen = HashSet::new();
+		for q in self.questions.iter() {
+			if !seen.insert(q.id.as_str()) {
+				return Err(format!("duplicate question id {:?}", q.id));
+			}
+			let choice = q.kind == QuestionKind::SingleChoice || q.kind == QuestionKind::MultiChoice;
+			if choice && q.options.is_empty() {
+				return Err(format!("question {:?} has no options", q.id));
+			}
+			if let (Some(min), Some(max)) = (q.min_choices, q.max_choices) {
+				if min > max {
+					return Err(format!("question {:?} has min_choices greater than max_choices", q.id));
+				}
+			}
+			if q.min_choices.map_or(false, |min| min < 0 || min as usize > q.options.len()) {
+				return Err(format!("question {:?} has min_choices outside 0..={}", q.id, q.options.len()));
+			}
+			if q.max_choices.map_or(false, |max| max < 1 || max as usize > q.options.len()) {
+				return Err(format!("question {:?} has max_choices outside 1..={}", q.id, q.options.len()));
+			}
+			if let (Some(min), Some(max)) = (q.min_value, q.max_value) {
+				if min > max {
+					return Err(format!("question {:?} has min_value greater than max_value", q.id));
+				}
+			}
+			if let Some(cond) = &q.visible_if {
+				// 只能依赖之前出现的选择题
+				let dep = match self.questions.iter().take_while(|x| x.id != q.id).find(|x| x.id == cond.question) {
+					Some(dep) if dep.kind == QuestionKind::SingleChoice || dep.kind == QuestionKind::MultiChoice => dep,
+					_ => return Err(format!("question {:?} depends on {:?} which is not an earlier choice question", q.id, cond.question)),
+				};
+				if cond.any_of.is_empty() {
+					return Err(format!("question {:?} has an empty visible_if.any_of", q.id));
+				}
+				if let Some(option) = cond.any_of.iter().find(|o| !dep.has_option(o)) {
+					return Err(format!("question {:?} depends on option {:?} which {:?} does not have", q.id, option, dep.id));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	pub fn question(&self, id: &str) -> Option<&Question> {
+		self.questions.iter().find(|q| q.id == id)
+	}
+
+	/// 校验问卷回答，paper_json 为 问题ID -> 回答 的JSON对象
+	pub fn validate_answers(&self, paper_json: &str) -> Result<(), Vec<ValidationIssue>> {
+		let answers = match serde_json::from_str::<Value>(paper_json) {
+			Ok(Value::Object(answers)) => answers,
+			Ok(_) => return Err(vec![ValidationIssue::new("paper_json", "INVALID_JSON", "paper_json must be a JSON object")]),
+			Err(e) => return Err(vec![ValidationIssue::new("paper_json", "INVALID_JSON", e.to_string())]),
+		};
+		let mut issues = vec![];
+		for key in answers.keys() {
+			if self.question(key).is_none() {
+				issues.push(ValidationIssue::new(key.as_str(), "UNKNOWN_QUESTION", format!("question {:?} does not exist", key)));
+			}
+		}
+		// 已回答的选项，用于判断后续问题是否显示
+		let mut chosen: HashMap<&str, Vec<&str>> = HashMap::new();
+		for q in self.questions.iter() {
+			// 空白的文本回答视为未回答
+			let answer = answers.get(&q.id).filter(|v| !v.is_null())
+				.filter(|v| q.kind != QuestionKind::Text || v.as_str().map_or(true, |t| !t.trim().is_empty()));
+			let visible = match &q.visible_if {
+				Some(cond) => chosen.get(cond.question.as_str()).map_or(false, |c| c.iter().any(|x| cond.any_of.iter().any(|y| y == x))),
+				None => true,
+			};
+			let answer = match (answer, visible) {
+				(None, true) if q.required => {
+					issues.push(ValidationIssue::new(q.id.as_str(), "REQUIRED", "this question is required"));
+					continue;
+				},
+				(None, _) => continue,
+				(Some(_), false) => {
+					issues.push(ValidationIssue::new(q.id.as_str(), "NOT_VISIBLE", "this question should not be answered"));
+					continue;
+				},
+				(Some(answer), true) => answer,
+			};
+			match q.check_answer(answer) {
+				Ok(options) => { chosen.insert(q.id.as_str(), options); },
+				Err(issue) => issues.push(issue),
+			}
+		}
+		if issues.is_empty() { Ok(()) } else { Err(issues) }
+	}
+}
+
+impl Question {
+	fn has_option(&self, id: &str) -> bool {
+		self.options.iter().any(|o| o.id == id)
+	}
+
+	/// 校验单个回答，返回选中的选项
+	fn check_answer<'a>(&self, answer: &'a Value) -> Result<Vec<&'a str>, ValidationIssue> {
+		let issue = |reason: &'static str, message: String| ValidationIssue::new(self.id.as_str(), reason, message);
+		match self.kind {
+			QuestionKind::SingleChoice => {
+				let option = answer.as_str().ok_or_else(|| issue("WRONG_TYPE", "expected an option id".to_string()))?;
+				if !self.has_option(option) {
+					return Err(issue("UNKNOWN_OPTION", format!("option {:?} does not exist", option)));
+				}
+				Ok(vec![option])
+			},
+			QuestionKind::MultiChoice => {
+				let items = answer.as_array().ok_or_else(|| issue("WRONG_TYPE", "expected a list of option ids".to_string()))?;
+				let mut options = vec![];
+				for item in items.iter() {
+					let option = item.as_str().ok_or_else(|| issue("WRONG_TYPE", "expected a list of option ids".to_string()))?;
+					if !self.has_option(option) {
+						return Err(issue("UNKNOWN_OPTION", format!("option {:?} does not exist", option)));
+					}
+					if options.contains(&option) {
+						return Err(issue("DUPLICATE", format!("option {:?} is chosen more than once", option)));
+					}
+					options.push(option);
+				}
+				let count = options.len() as i32;
+				if count < self.min_choices.unwrap_or(if self.required { 1 } else { 0 }) {
+					return Err(issue("TOO_FEW_CHOICES", format!("at least {} options must be chosen", self.min_choices.unwrap_or(1))));
+				}
+				if let Some(max) = self.max_choices {
+					if count > max {
+						return Err(issue("TOO_MANY_CHOICES", format!("at most {} options can be chosen", max)));
+					}
+				}
+				Ok(options)
+			},
+			QuestionKind::Text => {
+				let text = answer.as_str().ok_or_else(|| issue("WRONG_TYPE", "expected a string".to_string()))?;
+				let max = self.max_length.unwrap_or(DEFAULT_TEXT_MAX_LENGTH);
+				if text.chars().count() > max as usize {
+					return Err(issue("TOO_LONG", format!("answer must not exceed {} characters", max)));
+				}
+				Ok(vec![])
+			},
+			QuestionKind::Number => {
+				let value = answer.as_f64().ok_or_else(|| issue("WRONG_TYPE", "expected a number".to_string()))?;
+				if self.min_value.map_or(false, |min| value < min) || self.max_value.map_or(false, |max| value > max) {
+					return Err(issue("OUT_OF_RANGE", format!("answer must be between {:?} and {:?}", self.min_value, self.max_value)));
+				}
+				Ok(vec![])
+			},
+		}
+	}
+}
+
+pub fn init(questionnaire: Questionnaire) {
+	QUESTIONNAIRE.set(questionnaire).expect("questionnaire already initialized");
+}
+
+pub fn questionnaire() -> Option<&'static Questionnaire> {
+	QUESTIONNAIRE.get()
+}
+
+/// 未配置问卷定义时不做校验
+pub fn validate_paper_json(paper_json: &str) -> FieldResult<()> {
+	match questionnaire() {
+		Some(q) => q.validate_answers(paper_json).map_err(|issues| validation_error("INVALID_PAPER", "paper does not match the questionnaire", &issues)),
+		None => Ok(()),
+	}
+}
+
+// ------------------------------------------------
+// Root Quries
+// ------------------------------------------------
+
+pub async fn questionnaire_impl(context: &Context) -> FieldResult<Questionnaire> {
+	questionnaire().cloned().ok_or_else(|| gateway_error("QUESTIONNAIRE_UNAVAILABLE", "questionnaire is not configured"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(json: &str) -> Questionnaire {
+		serde_json::from_str(json).unwrap()
+	}
+
+	fn sample() -> Questionnaire {
+		let q = parse(r#"{
+			"version": "test",
+			"questions": [
+				{ "id": "q1", "title": "", "kind": "single_choice", "required": true,
+				  "options": [{ "id": "yes", "label": "" }, { "id": "no", "label": "" }] },
+				{ "id": "q2", "title": "", "kind": "multi_choice", "min_choices": 2, "max_choices": 3,
+				  "options": [{ "id": "a", "label": "" }, { "id": "b", "label": "" }, { "id": "c", "label": "" }, { "id": "d", "label": "" }],
+				  "visible_if": { "question": "q1", "any_of": ["yes"] } },
+				{ "id": "q3", "title": "", "kind": "text" },
+				{ "id": "q4", "title": "", "kind": "text", "required": true }
+			]
+		}"#);
+		q.check().unwrap();
+		q
+	}
+
+	fn reasons(paper_json: &str) -> Vec<(String, &'static str)> {
+		match sample().validate_answers(paper_json) {
+			Ok(()) => vec![],
+			Err(issues) => issues.into_iter().map(|i| (i.field, i.reason)).collect(),
+		}
+	}
+
+	fn issue(field: &str, reason: &'static str) -> Vec<(String, &'static str)> {
+		vec![(field.to_string(), reason)]
+	}
+
+	#[test]
+	fn accepts_valid_answers() {
+		assert_eq!(reasons(r#"{ "q1": "yes", "q2": ["a", "b"], "q3": "text", "q4": "text" }"#), vec![]);
+		assert_eq!(reasons(r#"{ "q1": "no", "q4": "text" }"#), vec![]);
+	}
+
+	#[test]
+	fn reports_missing_required_answer() {
+		assert_eq!(reasons(r#"{ "q3": "text", "q4": "text" }"#), issue("q1", "REQUIRED"));
+	}
+
+	#[test]
+	fn treats_blank_text_as_missing() {
+		assert_eq!(reasons(r#"{ "q1": "no", "q4": "" }"#), issue("q4", "REQUIRED"));
+		assert_eq!(reasons(r#"{ "q1": "no", "q4": "  " }"#), issue("q4", "REQUIRED"));
+		assert_eq!(reasons(r#"{ "q1": "no", "q3": "", "q4": "text" }"#), vec![]);
+	}
+
+	#[test]
+	fn reports_answer_to_hidden_question() {
+		assert_eq!(reasons(r#"{ "q1": "no", "q2": ["a", "b"], "q4": "text" }"#), issue("q2", "NOT_VISIBLE"));
+	}
+
+	#[test]
+	fn reports_unknown_option() {
+		assert_eq!(reasons(r#"{ "q1": "maybe", "q4": "text" }"#), issue("q1", "UNKNOWN_OPTION"));
+		assert_eq!(reasons(r#"{ "q1": "yes", "q2": ["a", "e"], "q4": "text" }"#), issue("q2", "UNKNOWN_OPTION"));
+	}
+
+	#[test]
+	fn reports_choice_count() {
+		assert_eq!(reasons(r#"{ "q1": "yes", "q2": ["a"], "q4": "text" }"#), issue("q2", "TOO_FEW_CHOICES"));
+		assert_eq!(reasons(r#"{ "q1": "yes", "q2": ["a", "b", "c", "d"], "q4": "text" }"#), issue("q2", "TOO_MANY_CHOICES"));
+	}
+
+	#[test]
+	fn rejects_inconsistent_definitions() {
+		let check = |questions: &str| parse(&format!(r#"{{ "version": "test", "questions": {} }}"#, questions)).check();
+		let options = r#"[{ "id": "a", "label": "" }, { "id": "b", "label": "" }]"#;
+		assert!(check(&format!(r#"[{{ "id": "q1", "title": "", "kind": "multi_choice", "min_choices": 2, "max_choices": 1, "options": {} }}]"#, options)).is_err());
+		assert!(check(&format!(r#"[{{ "id": "q1", "title": "", "kind": "multi_choice", "min_choices": 3, "options": {} }}]"#, options)).is_err());
+		for max in ["-1", "0", "3"].iter() {
+			assert!(check(&format!(r#"[{{ "id": "q1", "title": "", "kind": "multi_choice", "max_choices": {}, "options": {} }}]"#, max, options)).is_err());
+		}
+		assert!(check(r#"[{ "id": "q1", "title": "", "kind": "number", "min_value": 10, "max_value": 1 }]"#).is_err());
+		assert!(check(&format!(r#"[{{ "id": "q1", "title": "", "kind": "single_choice", "options": {} }},
+			{{ "id": "q2", "title": "", "kind": "text", "visible_if": {{ "question": "q1", "any_of": ["c"] }} }}]"#, options)).is_err());
+		assert!(check(&format!(r#"[{{ "id": "q1", "title": "", "kind": "single_choice", "options": {} }},
+			{{ "id": "q2", "title": "", "kind": "text", "visible_if": {{ "question": "q1", "any_of": ["a"] }} }}]"#, options)).is_ok());
+	}
+}
@@ -1,325 +1,1464 @@
 
-// use juniper::graphql_value;
-// use std::collections::BTreeMap;
-
-// use juniper::FieldResult;
-
-// use crate::common::*;
-
-// use serde_derive::{Serialize, Deserialize};
-// use bson::oid::ObjectId;
-
-// // ------------------------------------------------
-// // REST Schemas
-// // ------------------------------------------------
-
-// #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="单个问卷项目选项及对应结果")]
-// pub struct SinglePaperOptionResult {
-//     /// 选项名
-//     pub option_id: String,
-//     /// 结果数量（男）
-//     pub result_male: i32,
-//     /// 结果数量（女）
-//     pub result_female: i32
-// }
-
-// #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="单个问卷项目结果")]
-// pub struct SinglePaperResult {
-//     /// 项目名
-//     pub id: String,
-//     /// 回答结果（数值结果）
-//     pub answers_counts: Option<Vec<SinglePaperOptionResult>>,
-//     /// 回答结果（文本结果）
-//     pub answers_texts: Option<Vec<String>>
-// }
-
-// #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="问卷项目结果")]
-// pub struct PaperResults {
-//     /// 所有问卷项目结果
-//     pub results: Vec<SinglePaperResult>,
-//     /// 使用的过滤器
-//     pub filter_condtions: Option<FilterConditionsOutput>,
-//     /// 填写趋势
-//     pub trends: Option<Trends>,
-// }
-
-// #[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="单个过滤器条件")]
-// pub struct SingleFilterCondition {
-//     /// 来源
-//     pub section: VoteSection, 
-//     /// 条件
-//     pub condition: FilterConditionOp, 
-//     /// 左值
-//     pub lhs: String, 
-//     /// 右值
-//     pub rhs: String 
-// }
-
-// #[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="过滤器条件（所有条件与）")]
-// pub struct FilterConditions { // 
-//     pub conditions: Vec<SingleFilterCondition>
-// }
-
-// #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="(输出)单个过滤器条件")]
-// pub struct SingleFilterConditionOutput {
-//     /// 来源
-//     pub section: VoteSection, 
-//     /// 条件
-//     pub condition: FilterConditionOp, 
-//     /// 左值
-//     pub lhs: String, 
-//     /// 右值
-//     pub rhs: String 
-// }
-
-// #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="(输出)过滤器条件（所有条件与）")]
-// pub struct FilterConditionsOutput { // 
-//     pub conditions: Vec<SingleFilterConditionOutput>
-// }
-
-// impl SingleFilterConditionOutput {
-//     pub fn from_input(inp: &SingleFilterCondition) -> SingleFilterConditionOutput {
-//         SingleFilterConditionOutput {
-//             section: inp.section.clone(),
-//             condition: inp.condition.clone(),
-//             lhs: inp.lhs.clone(),
-//             rhs: inp.rhs.clone()
-//         }
-//     }
-// }
-
-// impl FilterConditionsOutput {
-//     pub fn from_input(inp: &FilterConditions) -> FilterConditionsOutput {
-//         FilterConditionsOutput {
-//             conditions: inp.conditions.iter().map(|ref x| SingleFilterConditionOutput::from_input(x)).collect::<Vec<_>>()
-//         }
-//     }
-// }
-
-// #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="投票理由集合")]
-// pub struct Reasons {
-//     pub reasons: Vec<String>
-// }
-
-// #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
-// #[graphql(description="投票时间趋势，返回开始到结束每小时的票数")]
-// pub struct Trends {
-//     /// 新增票数
-//     pub vote_inc: Option<Vec<i32>>,
-//     /// 减少票数
-//     pub vote_dec: Option<Vec<i32>>,
-//     /// 新增本命
-//     pub first_inc: Option<Vec<i32>>,
-//     /// 减少本命
-//     pub first_dev: Option<Vec<i32>>,
-//     /// 总票数
-//     pub vote_cum: Option<Vec<i32>>,
-//     /// 总本命数
-//     pub first_cum: Option<Vec<i32>>,
-//     /// 开始时间
-//     pub from_date: DateTime<Utc>,
-//     /// 结束时间
-//     pub to_date: DateTime<Utc>
-// }
-
-// #[derive(Clone, Serialize, Deserialize)]
-// pub struct SingleCharacterResult {
-//     /// 投票ID（2021）
-//     pub vote_id: i32,
-//     /// 名字
-//     pub name: String,
-//     /// 排名
-//     pub rank: i32,    
-//     /// 票数
-//     pub vote_count: i32, 
-//     /// 本名加权后票数
-//     pub vote_count_weighted: i32, 
-//     /// 票数占比
-//     pub vote_ratio: f64, 
-//     /// 本名票数
-//     pub vote_first_count: i32, 
-//     /// 本名占比
-//     pub vote_first_ratio: f64, 
-//     /// 男性票数
-//     pub male_count: i32, 
-//     /// 男性占比
-//     pub male_ratio: f64, 
-//     /// 女性票数
-//     pub female_count: i32, 
-//     /// 女性占比
-//     pub female_ratio: f64, 
-//     /// 前一次排名
-//     pub rank_prev: Option<i32>, 
-//     /// 投票理由
-//     pub reasons: Option<Reasons>, 
-//     /// 票数趋势
-//     pub trends: Option<Trends>,
-//     /// 根据投票人物过滤的问卷
-//     pub papers: Option<PaperResults>,
-//     /// 同投率
-//     pub cooccurrence_ratio: Option<f64> 
-// }
-
-// #[derive(Clone, Serialize, Deserialize)]
-// pub struct CharacterRankResult {
-//     /// 所有人物结果
-//     pub characters: Vec<SingleCharacterResult>,
-//     /// 使用的过滤器
-//     pub filter_condtions: Option<FilterConditionsOutput>
-// }
-
-
-// // ------------------------------------------------
-// // GQL Schemas
-// // ------------------------------------------------
-
-// #[juniper::object]
-// #[graphql(description="单个人物的结果")]
-// impl SingleCharacterResult {
-//     /// 名字
-//     pub fn name(&self) -> &String {
-//         &self.name
-//     }
-//     /// 排名
-//     pub fn rank(&self) -> &i32 {
-//         &self.rank
-//     }
-//     /// 票数
-//     pub fn vote_count(&self) -> &i32 {
-//         &self.vote_count
-//     }
-//     /// 本名加权后票数
-//     pub fn vote_count_weighted(&self) -> &i32 {
-//         &self.vote_count_weighted
-//     }
-//     /// 票数占比
-//     pub fn vote_ratio(&self) -> &f64 {
-//         &self.vote_ratio
-//     }
-//     /// 本名票数
-//     pub fn vote_first_count(&self) -> &i32 {
-//         &self.vote_first_count
-//     }
-//     /// 本名占比
-//     pub fn vote_first_ratio(&self) -> &f64 {
-//         &self.vote_first_ratio
-//     }
-//     /// 男性票数
-//     pub fn male_count(&self) -> &i32 {
-//         &self.male_count
-//     }
-//     /// 男性占比
-//     pub fn male_ratio(&self) -> &f64 {
-//         &self.male_ratio
-//     }
-//     /// 女性票数
-//     pub fn female_count(&self) -> &i32 {
-//         &self.female_count
-//     }
-//     /// 女性占比
-//     pub fn female_ratio(&self) -> &f64 {
-//         &self.female_ratio
-//     }
-//     /// 前一次排名
-//     pub fn rank_prev(&self) -> Option<i32> {
-//         None
-//     }
-//     /// 投票理由
-//     pub fn reasons(&self) -> Option<Reasons> {
-//         None
-//     }
-//     /// 票数趋势
-//     pub fn trends(&self) -> Option<Trends> {
-//         None
-//     }
-//     /// 根据投票人物过滤的问卷
-//     pub fn papers(&self) -> Option<PaperResults> {
-//         None
-//     }
-//     /// 同投率
-//     pub fn cooccurrence_ratio(&self) -> Option<f64> {
-//         None
-//     }
-// }
-
-// #[juniper::object]
-// #[graphql(description="人物的结果")]
-// impl CharacterRankResult {
-//     /// 所有人物结果
-//     pub fn characters(&self) -> &Vec<SingleCharacterResult> {
-//         &self.characters
-//     }
-//     /// 使用的过滤器
-//     pub fn filter_condtions(&self) -> Option<FilterConditionsOutput> {
-//         None
-//     }
-// }
-
-// // ------------------------------------------------
-// // Root Quries
-// // ------------------------------------------------
-
-// use crate::services::*;
-
-// // pub fn character_rank_impl(filters: Option<FilterConditions>) -> FieldResult<CharacterRankResult> {
-
-// // }
-
-// pub fn character_reasons_impl(name: String) -> FieldResult<Reasons> {
-//     Ok(Reasons { reasons: vec![] })
-// }
-
-// pub fn single_character_result_impl(name: String, filter: Option<FilterConditions>) -> FieldResult<SingleCharacterResult> {
-//     Ok(SingleCharacterResult {
-//         vote_id: 2020,
-//         name: "博丽灵梦".into(),
-//         rank: 1,
-//         vote_count: 6000,
-//         vote_count_weighted: 7000,
-//         vote_ratio: 0.8,
-//         vote_first_count: 600,
-//         vote_first_ratio: 0.1,
-//         male_count: 5000,
-//         male_ratio: 0.8333333333,
-//         female_count: 1000,
-//         female_ratio: 0.1666666666,
-//         rank_prev: None,
-//         reasons: None,
-//         trends: None,
-//         papers: None,
-//         cooccurrence_ratio: None
-//     })
-// }
-
-// pub fn character_rank_result_impl(filter: Option<FilterConditions>) -> FieldResult<CharacterRankResult> {
-//     Ok(CharacterRankResult {
-//         characters: vec![SingleCharacterResult {
-//             vote_id: 2020,
-//             name: "博丽灵梦".into(),
-//             rank: 1,
-//             vote_count: 6000,
-//             vote_count_weighted: 7000,
-//             vote_ratio: 0.8,
-//             vote_first_count: 600,
-//             vote_first_ratio: 0.1,
-//             male_count: 5000,
-//             male_ratio: 0.8333333333,
-//             female_count: 1000,
-//             female_ratio: 0.1666666666,
-//             rank_prev: None,
-//             reasons: None,
-//             trends: None,
-//             papers: None,
-//             cooccurrence_ratio: None
-//         }],
-//         filter_condtions: match filter { Some(ref x) => Some(FilterConditionsOutput::from_input(&x)), None => None }
-//     })
-// }
+use juniper::{FieldError, FieldResult};
+
+use chrono::{DateTime, Duration, FixedOffset, TimeZone, Timelike, Utc};
+
+use crate::common::{VOTE_UTC_OFFSET_MINUTES, gateway_error, vote_end, vote_start};
+use crate::context::Context;
+use crate::submit_handler::canonical_cp_members;
+use crate::i18n::Language;
+use crate::questionnaire::questionnaire;
+use crate::vote_data::{VotableCharacter, VotableMusic, catalog, is_votable_character, is_votable_music, is_votable_work};
+
+use serde_derive::{Serialize, Deserialize};
+
+// ------------------------------------------------
+// REST Schemas
+// ------------------------------------------------
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[graphql(description="投票项目")]
+pub enum VoteSection {
+    /// 人物
+    Character,
+    /// 音乐
+    Music,
+    /// CP
+    CP,
+    /// 问卷
+    Paper,
+    /// 作品
+    Work
+}
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[graphql(description="过滤条件")]
+pub enum FilterConditionOp {
+    /// 等于
+    Eq,
+    /// 不等于
+    Neq,
+    /// 包含（问卷多选/文本）
+    Contains,
+    /// 属于右值中的任意一个（逗号分隔）
+    In,
+    /// 大于（问卷数值）
+    Gt,
+    /// 小于（问卷数值）
+    Lt
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[graphql(description="单个过滤器条件")]
+pub struct SingleFilterCondition {
+    /// 来源
+    pub section: VoteSection,
+    /// 条件
+    pub condition: FilterConditionOp,
+    /// 左值，人物/音乐为ID，CP为逗号分隔的成员ID，问卷为问题ID
+    pub lhs: String,
+    /// 右值，人物/音乐/CP为 voted 或 first，问卷为答案
+    pub rhs: String
+}
+
+#[derive(juniper::GraphQLInputObject, Clone, Debug, Serialize, Deserialize)]
+#[graphql(description="过滤器条件，conditions 与 and 全部满足、or 满足其一、not 不满足")]
+pub struct FilterConditions {
+    /// 所有条件与
+    pub conditions: Option<Vec<SingleFilterCondition>>,
+    /// 子过滤器全部满足
+    pub and: Option<Vec<FilterConditions>>,
+    /// 子过滤器满足其一
+    pub or: Option<Vec<FilterConditions>>,
+    /// 子过滤器不满足
+    pub not: Option<Box<FilterConditions>>
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="(输出)单个过滤器条件")]
+pub struct SingleFilterConditionOutput {
+    /// 来源
+    pub section: VoteSection,
+    /// 条件
+    pub condition: FilterConditionOp,
+    /// 左值
+    pub lhs: String,
+    /// 右值
+    pub rhs: String
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="(输出)规范化后的过滤器条件")]
+pub struct FilterConditionsOutput {
+    /// 所有条件与
+    pub conditions: Vec<SingleFilterConditionOutput>,
+    /// 子过滤器全部满足
+    pub and: Vec<FilterConditionsOutput>,
+    /// 子过滤器满足其一
+    pub or: Vec<FilterConditionsOutput>,
+    /// 子过滤器不满足
+    pub not: Option<Box<FilterConditionsOutput>>
+}
+
+impl SingleFilterConditionOutput {
+    pub fn from_input(inp: &SingleFilterCondition) -> SingleFilterConditionOutput {
+        SingleFilterConditionOutput {
+            section: inp.section,
+            condition: inp.condition,
+            lhs: inp.lhs.clone(),
+            rhs: inp.rhs.clone()
+        }
+    }
+}
+
+impl FilterConditionsOutput {
+    pub fn from_input(inp: &FilterConditions) -> FilterConditionsOutput {
+        FilterConditionsOutput {
+            conditions: inp.conditions.iter().flatten().map(|ref x| SingleFilterConditionOutput::from_input(x)).collect::<Vec<_>>(),
+            and: inp.and.iter().flatten().map(FilterConditionsOutput::from_input).collect::<Vec<_>>(),
+            or: inp.or.iter().flatten().map(FilterConditionsOutput::from_input).collect::<Vec<_>>(),
+            not: inp.not.as_ref().map(|x| Box::new(FilterConditionsOutput::from_input(x)))
+        }
+    }
+}
+
+// ------------------------------------------------
+// Filter normalization
+// ------------------------------------------------
+
+/// 过滤器最大嵌套层数
+const MAX_FILTER_DEPTH: usize = 8;
+/// 过滤器最多条件数
+const MAX_FILTER_CONDITIONS: usize = 64;
+/// 过滤器最多子过滤器数（含空的子过滤器）
+const MAX_FILTER_NODES: usize = 64;
+
+fn filter_error(message: String) -> FieldError {
+    gateway_error("INVALID_FILTER", &message)
+}
+
+/// 校验某个投票项目中的条目ID，CP为逗号分隔的成员ID，返回规范化后的ID
+fn normalize_entry_id(section: VoteSection, id: &str) -> Result<String, String> {
+    let id = id.trim();
+    match section {
+        VoteSection::Character => {
+            if !is_votable_character(id) {
+                return Err(format!("unknown character {:?}", id));
+            }
+            Ok(id.to_string())
+        },
+        VoteSection::Music => {
+            if !is_votable_music(id) {
+                return Err(format!("unknown music {:?}", id));
+            }
+            Ok(id.to_string())
+        },
+        VoteSection::CP => {
+            let members: Vec<&str> = id.split(',').map(|x| x.trim()).collect();
+            if members.len() < 2 || members.len() > 3 {
+                return Err(format!("CP {:?} must have 2 or 3 members", id));
+            }
+            if let Some(m) = members.iter().find(|m| !is_votable_character(m)) {
+                return Err(format!("unknown character {:?} in CP {:?}", m, id));
+            }
+            let members = canonical_cp_members(members[0], members[1], members.get(2).copied());
+            if members.windows(2).any(|w| w[0] == w[1]) {
+                return Err(format!("CP {:?} has duplicate members", id));
+            }
+            Ok(members.join(","))
+        },
+        VoteSection::Work => {
+            if !is_votable_work(id) {
+                return Err(format!("unknown work {:?}", id));
+            }
+            Ok(id.to_string())
+        },
+        VoteSection::Paper => {
+            if id.is_empty() {
+                return Err("paper question id must not be empty".to_string());
+            }
+            // 未配置问卷定义时无法校验问题ID
+            if questionnaire().map_or(false, |q| q.question(id).is_none()) {
+                return Err(format!("unknown paper question {:?}", id));
+            }
+            Ok(id.to_string())
+        }
+    }
+}
+
+impl SingleFilterCondition {
+    /// 校验单个条件并规范化左右值
+    fn normalize(&self) -> FieldResult<SingleFilterCondition> {
+        let lhs = normalize_entry_id(self.section, &self.lhs).map_err(filter_error)?;
+        let rhs = self.rhs.trim();
+        let rhs = match (self.section, self.condition) {
+            (VoteSection::Paper, FilterConditionOp::Gt) | (VoteSection::Paper, FilterConditionOp::Lt) => {
+                if rhs.parse::<f64>().is_err() {
+                    return Err(filter_error(format!("{:?} is not a number", rhs)));
+                }
+                rhs.to_string()
+            },
+            (VoteSection::Paper, FilterConditionOp::In) => {
+                let mut options: Vec<&str> = rhs.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).collect();
+                if options.is_empty() {
+                    return Err(filter_error("rhs of in condition must list at least one answer".to_string()));
+                }
+                options.sort();
+                options.dedup();
+                options.join(",")
+            },
+            (VoteSection::Paper, _) => rhs.to_string(),
+            (_, FilterConditionOp::Eq) | (_, FilterConditionOp::Neq) => {
+                if rhs != "voted" && rhs != "first" {
+                    return Err(filter_error(format!("rhs of {:?} condition must be \"voted\" or \"first\"", self.section)));
+                }
+                rhs.to_string()
+            },
+            (section, op) => {
+                return Err(filter_error(format!("{:?} is not supported for {:?}", op, section)));
+            }
+        };
+        Ok(SingleFilterCondition {
+            section: self.section,
+            condition: self.condition,
+            lhs: lhs,
+            rhs: rhs
+        })
+    }
+}
+
+impl FilterConditions {
+    fn is_only_not(&self) -> bool {
+        self.conditions.is_none() && self.and.is_none() && self.or.is_none() && self.not.is_some()
+    }
+
+    /// 校验并规范化过滤器，返回 None 表示不过滤任何投票
+    fn normalize(&self, depth: usize, count: &mut usize, nodes: &mut usize) -> FieldResult<Option<FilterConditions>> {
+        if depth > MAX_FILTER_DEPTH {
+            return Err(filter_error(format!("filter is nested deeper than {} levels", MAX_FILTER_DEPTH)));
+        }
+        *nodes += 1;
+        if *nodes > MAX_FILTER_NODES {
+            return Err(filter_error(format!("filter has more than {} sub-filters", MAX_FILTER_NODES)));
+        }
+        let mut conditions = vec![];
+        let mut and = vec![];
+        let mut or = vec![];
+        let mut not = None;
+        for c in self.conditions.iter().flatten() {
+            *count += 1;
+            if *count > MAX_FILTER_CONDITIONS {
+                return Err(filter_error(format!("filter has more than {} conditions", MAX_FILTER_CONDITIONS)));
+            }
+            conditions.push(c.normalize()?);
+        }
+        for child in self.and.iter().flatten() {
+            if let Some(child) = child.normalize(depth + 1, count, nodes)? {
+                and.push(child);
+            }
+        }
+        if let Some(children) = &self.or {
+            if children.is_empty() {
+                return Err(filter_error("or must contain at least one filter".to_string()));
+            }
+            let mut matches_all = false;
+            for child in children.iter() {
+                match child.normalize(depth + 1, count, nodes)? {
+                    Some(child) => or.push(child),
+                    None => matches_all = true
+                }
+            }
+            if matches_all {
+                // 有一个分支不过滤任何投票，整个或关系恒成立
+                or.clear();
+            } else if or.len() == 1 {
+                and.append(&mut or);
+            }
+        }
+        if let Some(child) = &self.not {
+            match child.normalize(depth + 1, count, nodes)? {
+                None => return Err(filter_error("not of an empty filter would exclude every vote".to_string())),
+                Some(child) if child.is_only_not() => and.push(*child.not.unwrap()),
+                Some(child) => not = Some(Box::new(child))
+            }
+        }
+        // 只有与关系的子过滤器直接展开到当前层
+        let mut nested = vec![];
+        for child in and {
+            if child.or.is_none() && child.not.is_none() {
+                conditions.extend(child.conditions.unwrap_or_default());
+                nested.extend(child.and.unwrap_or_default());
+            } else {
+                nested.push(child);
+            }
+        }
+        conditions.sort();
+        conditions.dedup();
+        if conditions.is_empty() && or.is_empty() && not.is_none() && nested.len() <= 1 {
+            return Ok(nested.pop());
+        }
+        Ok(Some(FilterConditions {
+            conditions: if conditions.is_empty() { None } else { Some(conditions) },
+            and: if nested.is_empty() { None } else { Some(nested) },
+            or: if or.is_empty() { None } else { Some(or) },
+            not: not
+        }))
+    }
+}
+
+/// 校验过滤器中的ID并规范化，结果会原样发给 result-query 并回显给客户端
+pub fn normalize_filter(filter: Option<FilterConditions>) -> FieldResult<Option<FilterConditions>> {
+    match filter {
+        Some(filter) => filter.normalize(0, &mut 0, &mut 0),
+        None => Ok(None)
+    }
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="投票理由集合")]
+pub struct Reasons {
+    pub reasons: Vec<String>
+}
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(description="投票理由排序方式")]
+pub enum ReasonOrder {
+    /// 最新提交在前
+    Newest,
+    /// 最早提交在前
+    Oldest,
+    /// 最长的理由在前
+    Longest
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="单条投票理由")]
+pub struct Reason {
+    /// 理由内容
+    pub content: String,
+    /// 是否为本命
+    pub first: bool,
+    /// 提交时间
+    pub created_at: DateTime<Utc>
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="投票理由分页中的一项")]
+pub struct ReasonEdge {
+    /// 分页游标
+    pub cursor: String,
+    pub node: Reason
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="分页信息")]
+pub struct PageInfo {
+    pub has_next_page: bool,
+    pub has_previous_page: bool,
+    pub start_cursor: Option<String>,
+    pub end_cursor: Option<String>
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="投票理由分页")]
+pub struct ReasonConnection {
+    pub edges: Vec<ReasonEdge>,
+    pub page_info: PageInfo,
+    /// 满足条件的理由总数
+    pub total_count: i32
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReasonsPageRest {
+    /// 最多比请求的数量多一条，用于判断是否还有下一页
+    pub edges: Vec<ReasonEdge>,
+    pub total_count: i32
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="投票时间趋势，返回开始到结束每小时的票数")]
+pub struct Trends {
+    /// 新增票数
+    pub vote_inc: Option<Vec<i32>>,
+    /// 减少票数
+    pub vote_dec: Option<Vec<i32>>,
+    /// 新增本命
+    pub first_inc: Option<Vec<i32>>,
+    /// 减少本命
+    pub first_dev: Option<Vec<i32>>,
+    /// 总票数
+    pub vote_cum: Option<Vec<i32>>,
+    /// 总本命数
+    pub first_cum: Option<Vec<i32>>,
+    /// 开始时间
+    pub from_date: DateTime<Utc>,
+    /// 结束时间
+    pub to_date: DateTime<Utc>
+}
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(description="趋势统计粒度")]
+pub enum TrendBucket {
+    /// 每小时
+    Hour,
+    /// 每天
+    Day
+}
+
+impl Trends {
+    /// 上游未返回累计值时由增减量推算
+    fn fill_cumulative(&mut self) {
+        fn cumulative(inc: &Option<Vec<i32>>, dec: &Option<Vec<i32>>) -> Option<Vec<i32>> {
+            let inc = inc.as_ref()?;
+            let mut total = 0;
+            Some(inc.iter().enumerate().map(|(i, x)| {
+                total += x - dec.as_ref().and_then(|d| d.get(i)).copied().unwrap_or(0);
+                total
+            }).collect())
+        }
+        if self.vote_cum.is_none() {
+            self.vote_cum = cumulative(&self.vote_inc, &self.vote_dec);
+        }
+        if self.first_cum.is_none() {
+            self.first_cum = cumulative(&self.first_inc, &self.first_dev);
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="单个问卷项目选项及对应结果")]
+pub struct SinglePaperOptionResult {
+    /// 选项名
+    pub option_id: String,
+    /// 结果数量（男）
+    pub result_male: i32,
+    /// 结果数量（女）
+    pub result_female: i32,
+    /// 结果数量（全部，包括未填写性别）
+    pub result_total: i32
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="单个问卷项目结果")]
+pub struct SinglePaperResult {
+    /// 项目名
+    pub id: String,
+    /// 回答人数
+    pub answer_count: i32,
+    /// 回答结果（数值结果）
+    pub answers_counts: Option<Vec<SinglePaperOptionResult>>,
+    /// 回答结果（文本结果，随机抽样）
+    pub answers_texts: Option<Vec<String>>
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="问卷项目结果")]
+pub struct PaperResults {
+    /// 所有问卷项目结果
+    pub results: Vec<SinglePaperResult>,
+    /// 使用的过滤器
+    #[serde(default)]
+    pub filter_condtions: Option<FilterConditionsOutput>,
+    /// 填写趋势
+    #[serde(default)]
+    pub trends: Option<Trends>,
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="同投矩阵中的一项")]
+pub struct CooccurrenceEntry {
+    /// 来源ID
+    pub source: String,
+    /// 目标ID
+    pub target: String,
+    /// 同时投了来源和目标的人数
+    pub count: i32,
+    /// 投了来源的人中也投了目标的比例
+    pub ratio: f64
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="同投矩阵中来源的投票人数")]
+pub struct CooccurrenceSource {
+    /// 来源ID
+    pub id: String,
+    /// 投了来源的人数
+    pub vote_count: i32
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="同投矩阵（稀疏），每个来源只保留同投人数最多的若干目标")]
+pub struct CooccurrenceMatrix {
+    /// 来源项目
+    pub section: VoteSection,
+    /// 目标项目
+    pub target_section: VoteSection,
+    /// 所有来源
+    pub sources: Vec<CooccurrenceSource>,
+    /// 非零项
+    pub entries: Vec<CooccurrenceEntry>,
+    /// 使用的过滤器
+    #[serde(default)]
+    pub filter_condtions: Option<FilterConditionsOutput>
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="往届投票中的结果")]
+pub struct PollHistoryEntry {
+    /// 届次（年份）
+    pub year: i32,
+    /// 排名
+    pub rank: i32,
+    /// 票数
+    pub vote_count: i32,
+    /// 本名票数
+    pub vote_first_count: i32,
+    /// 票数占比
+    pub vote_ratio: f64
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SingleCharacterResult {
+    /// 投票数据中的ID
+    pub id: String,
+    /// 排名
+    pub rank: i32,
+    /// 票数
+    pub vote_count: i32,
+    /// 本名加权后票数
+    pub vote_count_weighted: i32,
+    /// 票数占比
+    pub vote_ratio: f64,
+    /// 本名票数
+    pub vote_first_count: i32,
+    /// 本名占比
+    pub vote_first_ratio: f64,
+    /// 男性票数
+    pub male_count: i32,
+    /// 男性占比
+    pub male_ratio: f64,
+    /// 女性票数
+    pub female_count: i32,
+    /// 女性占比
+    pub female_ratio: f64,
+    /// 前一次排名
+    pub rank_prev: Option<i32>,
+    /// 投票理由
+    #[serde(default)]
+    pub reasons: Option<Reasons>,
+    /// 查询时使用的过滤器，趋势等子查询默认沿用
+    #[serde(skip)]
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CharacterRankResult {
+    /// 所有人物结果
+    pub characters: Vec<SingleCharacterResult>,
+    /// 使用的过滤器
+    #[serde(default)]
+    pub filter_condtions: Option<FilterConditionsOutput>
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SingleMusicResult {
+    /// 投票数据中的ID
+    pub id: String,
+    /// 排名
+    pub rank: i32,
+    /// 票数
+    pub vote_count: i32,
+    /// 本名加权后票数
+    pub vote_count_weighted: i32,
+    /// 票数占比
+    pub vote_ratio: f64,
+    /// 本名票数
+    pub vote_first_count: i32,
+    /// 本名占比
+    pub vote_first_ratio: f64,
+    /// 男性票数
+    pub male_count: i32,
+    /// 男性占比
+    pub male_ratio: f64,
+    /// 女性票数
+    pub female_count: i32,
+    /// 女性占比
+    pub female_ratio: f64,
+    /// 前一次排名
+    pub rank_prev: Option<i32>,
+    /// 投票理由
+    #[serde(default)]
+    pub reasons: Option<Reasons>,
+    /// 查询时使用的过滤器，趋势等子查询默认沿用
+    #[serde(skip)]
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MusicRankResult {
+    /// 所有音乐结果
+    pub musics: Vec<SingleMusicResult>,
+    /// 使用的过滤器
+    #[serde(default)]
+    pub filter_condtions: Option<FilterConditionsOutput>
+}
+
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="CP中某一方作为主动方的票数")]
+pub struct CPActiveCount {
+    /// 主动方人物ID
+    pub id: String,
+    /// 票数
+    pub vote_count: i32
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SingleCPResult {
+    /// 人物A（按ID排序后）
+    pub id_a: String,
+    /// 人物B（按ID排序后）
+    pub id_b: String,
+    /// 人物C（可选）
+    pub id_c: Option<String>,
+    /// 各主动方票数，未指定主动方的票不计入
+    #[serde(default)]
+    pub active_counts: Vec<CPActiveCount>,
+    /// 排名
+    pub rank: i32,
+    /// 票数
+    pub vote_count: i32,
+    /// 本名加权后票数
+    pub vote_count_weighted: i32,
+    /// 票数占比
+    pub vote_ratio: f64,
+    /// 本名票数
+    pub vote_first_count: i32,
+    /// 本名占比
+    pub vote_first_ratio: f64,
+    /// 男性票数
+    pub male_count: i32,
+    /// 男性占比
+    pub male_ratio: f64,
+    /// 女性票数
+    pub female_count: i32,
+    /// 女性占比
+    pub female_ratio: f64,
+    /// 前一次排名
+    pub rank_prev: Option<i32>,
+    /// 查询时使用的过滤器，趋势等子查询默认沿用
+    #[serde(skip)]
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CPRankResult {
+    /// 所有CP结果
+    pub cps: Vec<SingleCPResult>,
+    /// 使用的过滤器
+    #[serde(default)]
+    pub filter_condtions: Option<FilterConditionsOutput>
+}
+
+impl SingleCPResult {
+    fn members(&self) -> Vec<String> {
+        canonical_cp_members(&self.id_a, &self.id_b, self.id_c.as_deref())
+    }
+
+    /// 上游可能按投票时的顺序返回成员，这里统一成与查询相同的排序
+    fn canonicalize(&mut self) {
+        let mut members = self.members().into_iter();
+        self.id_a = members.next().unwrap();
+        self.id_b = members.next().unwrap();
+        self.id_c = members.next();
+    }
+}
+
+
+// ------------------------------------------------
+// GQL Schemas
+// ------------------------------------------------
+
+#[juniper::graphql_object(Context = Context, description = "单个人物的结果")]
+impl SingleCharacterResult {
+    /// 人物ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    /// 名字，lang 未指定时使用请求协商的语言
+    pub fn name(&self, context: &Context, lang: Option<Language>) -> String {
+        catalog().character(&self.id).map_or_else(|| self.id.clone(), |c| c.names.resolve(lang.unwrap_or(context.lang), &c.name).to_string())
+    }
+    /// 投票数据中的人物
+    pub fn character(&self) -> Option<VotableCharacter> {
+        catalog().character(&self.id).cloned()
+    }
+    /// 排名
+    pub fn rank(&self) -> i32 {
+        self.rank
+    }
+    /// 票数
+    pub fn vote_count(&self) -> i32 {
+        self.vote_count
+    }
+    /// 本名加权后票数
+    pub fn vote_count_weighted(&self) -> i32 {
+        self.vote_count_weighted
+    }
+    /// 票数占比
+    pub fn vote_ratio(&self) -> f64 {
+        self.vote_ratio
+    }
+    /// 本名票数
+    pub fn vote_first_count(&self) -> i32 {
+        self.vote_first_count
+    }
+    /// 本名占比
+    pub fn vote_first_ratio(&self) -> f64 {
+        self.vote_first_ratio
+    }
+    /// 男性票数
+    pub fn male_count(&self) -> i32 {
+        self.male_count
+    }
+    /// 男性占比
+    pub fn male_ratio(&self) -> f64 {
+        self.male_ratio
+    }
+    /// 女性票数
+    pub fn female_count(&self) -> i32 {
+        self.female_count
+    }
+    /// 女性占比
+    pub fn female_ratio(&self) -> f64 {
+        self.female_ratio
+    }
+    /// 前一次排名
+    pub fn rank_prev(&self) -> Option<i32> {
+        self.rank_prev
+    }
+    /// 往届结果，years 为空时返回所有参加过的届次
+    pub async fn history(&self, context: &Context, years: Option<Vec<i32>>) -> FieldResult<Vec<PollHistoryEntry>> {
+        history_impl(context, VoteSection::Character, self.id.clone(), years).await
+    }
+    /// 投票理由
+    pub fn reasons(&self) -> Option<Reasons> {
+        self.reasons.clone()
+    }
+    /// 票数趋势，默认统计整个投票期间（GMT+8）每小时的票数
+    pub async fn trends(&self, context: &Context, bucket: Option<TrendBucket>, utc_offset_minutes: Option<i32>, from_date: Option<DateTime<Utc>>, to_date: Option<DateTime<Utc>>, filter: Option<FilterConditions>) -> FieldResult<Trends> {
+        trends_impl(context, VoteSection::Character, self.id.clone(), bucket, utc_offset_minutes, from_date, to_date, filter.or_else(|| self.filter.clone())).await
+    }
+    /// 根据投票人物过滤的问卷
+    pub async fn papers(&self, context: &Context, question_ids: Option<Vec<String>>, text_sample_size: Option<i32>) -> FieldResult<PaperResults> {
+        let voted = SingleFilterCondition {
+            section: VoteSection::Character,
+            condition: FilterConditionOp::Eq,
+            lhs: self.id.clone(),
+            rhs: "voted".to_string()
+        };
+        let filter = FilterConditions {
+            conditions: Some(vec![voted]),
+            and: self.filter.clone().map(|x| vec![x]),
+            or: None,
+            not: None
+        };
+        paper_results_impl(context, Some(filter), question_ids, text_sample_size).await
+    }
+    /// 同投率：投了该人物的人中也投了目标的比例，目标项目默认与来源相同
+    pub async fn cooccurrence_ratio(&self, context: &Context, target_id: String, target_section: Option<VoteSection>) -> FieldResult<Option<f64>> {
+        let matrix = cooccurrence_impl(context, VoteSection::Character, vec![self.id.clone()], target_section, Some(vec![target_id]), Some(1), self.filter.clone()).await?;
+        Ok(matrix.entries.first().map(|x| x.ratio))
+    }
+}
+
+#[juniper::graphql_object(Context = Context, description = "人物的结果")]
+impl CharacterRankResult {
+    /// 所有人物结果
+    pub fn characters(&self) -> &Vec<SingleCharacterResult> {
+        &self.characters
+    }
+    /// 使用的过滤器
+    pub fn filter_condtions(&self) -> Option<FilterConditionsOutput> {
+        self.filter_condtions.clone()
+    }
+}
+
+#[juniper::graphql_object(Context = Context, description = "单个音乐的结果")]
+impl SingleMusicResult {
+    /// 音乐ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    /// 名字，lang 未指定时使用请求协商的语言
+    pub fn name(&self, context: &Context, lang: Option<Language>) -> String {
+        catalog().music(&self.id).map_or_else(|| self.id.clone(), |m| m.names.resolve(lang.unwrap_or(context.lang), &m.name).to_string())
+    }
+    /// 投票数据中的音乐
+    pub fn music(&self) -> Option<VotableMusic> {
+        catalog().music(&self.id).cloned()
+    }
+    /// 排名
+    pub fn rank(&self) -> i32 {
+        self.rank
+    }
+    /// 票数
+    pub fn vote_count(&self) -> i32 {
+        self.vote_count
+    }
+    /// 本名加权后票数
+    pub fn vote_count_weighted(&self) -> i32 {
+        self.vote_count_weighted
+    }
+    /// 票数占比
+    pub fn vote_ratio(&self) -> f64 {
+        self.vote_ratio
+    }
+    /// 本名票数
+    pub fn vote_first_count(&self) -> i32 {
+        self.vote_first_count
+    }
+    /// 本名占比
+    pub fn vote_first_ratio(&self) -> f64 {
+        self.vote_first_ratio
+    }
+    /// 男性票数
+    pub fn male_count(&self) -> i32 {
+        self.male_count
+    }
+    /// 男性占比
+    pub fn male_ratio(&self) -> f64 {
+        self.male_ratio
+    }
+    /// 女性票数
+    pub fn female_count(&self) -> i32 {
+        self.female_count
+    }
+    /// 女性占比
+    pub fn female_ratio(&self) -> f64 {
+        self.female_ratio
+    }
+    /// 前一次排名
+    pub fn rank_prev(&self) -> Option<i32> {
+        self.rank_prev
+    }
+    /// 往届结果，years 为空时返回所有参加过的届次
+    pub async fn history(&self, context: &Context, years: Option<Vec<i32>>) -> FieldResult<Vec<PollHistoryEntry>> {
+        history_impl(context, VoteSection::Music, self.id.clone(), years).await
+    }
+    /// 投票理由
+    pub fn reasons(&self) -> Option<Reasons> {
+        self.reasons.clone()
+    }
+    /// 票数趋势，默认统计整个投票期间（GMT+8）每小时的票数
+    pub async fn trends(&self, context: &Context, bucket: Option<TrendBucket>, utc_offset_minutes: Option<i32>, from_date: Option<DateTime<Utc>>, to_date: Option<DateTime<Utc>>, filter: Option<FilterConditions>) -> FieldResult<Trends> {
+        trends_impl(context, VoteSection::Music, self.id.clone(), bucket, utc_offset_minutes, from_date, to_date, filter.or_else(|| self.filter.clone())).await
+    }
+    /// 同投率：投了该音乐的人中也投了目标的比例，目标项目默认与来源相同
+    pub async fn cooccurrence_ratio(&self, context: &Context, target_id: String, target_section: Option<VoteSection>) -> FieldResult<Option<f64>> {
+        let matrix = cooccurrence_impl(context, VoteSection::Music, vec![self.id.clone()], target_section, Some(vec![target_id]), Some(1), self.filter.clone()).await?;
+        Ok(matrix.entries.first().map(|x| x.ratio))
+    }
+}
+
+#[juniper::graphql_object(Context = Context, description = "音乐的结果")]
+impl MusicRankResult {
+    /// 所有音乐结果
+    pub fn musics(&self) -> &Vec<SingleMusicResult> {
+        &self.musics
+    }
+    /// 使用的过滤器
+    pub fn filter_condtions(&self) -> Option<FilterConditionsOutput> {
+        self.filter_condtions.clone()
+    }
+}
+
+#[juniper::graphql_object(Context = Context, description = "单个CP的结果")]
+impl SingleCPResult {
+    /// 人物A（按ID排序后）
+    pub fn id_a(&self) -> &str {
+        &self.id_a
+    }
+    /// 人物B（按ID排序后）
+    pub fn id_b(&self) -> &str {
+        &self.id_b
+    }
+    /// 人物C（可选）
+    pub fn id_c(&self) -> Option<String> {
+        self.id_c.clone()
+    }
+    /// 各主动方票数
+    pub fn active_counts(&self) -> &Vec<CPActiveCount> {
+        &self.active_counts
+    }
+    /// 排名
+    pub fn rank(&self) -> i32 {
+        self.rank
+    }
+    /// 票数
+    pub fn vote_count(&self) -> i32 {
+        self.vote_count
+    }
+    /// 本名加权后票数
+    pub fn vote_count_weighted(&self) -> i32 {
+        self.vote_count_weighted
+    }
+    /// 票数占比
+    pub fn vote_ratio(&self) -> f64 {
+        self.vote_ratio
+    }
+    /// 本名票数
+    pub fn vote_first_count(&self) -> i32 {
+        self.vote_first_count
+    }
+    /// 本名占比
+    pub fn vote_first_ratio(&self) -> f64 {
+        self.vote_first_ratio
+    }
+    /// 男性票数
+    pub fn male_count(&self) -> i32 {
+        self.male_count
+    }
+    /// 男性占比
+    pub fn male_ratio(&self) -> f64 {
+        self.male_ratio
+    }
+    /// 女性票数
+    pub fn female_count(&self) -> i32 {
+        self.female_count
+    }
+    /// 女性占比
+    pub fn female_ratio(&self) -> f64 {
+        self.female_ratio
+    }
+    /// 前一次排名
+    pub fn rank_prev(&self) -> Option<i32> {
+        self.rank_prev
+    }
+    /// 往届结果，years 为空时返回所有参加过的届次
+    pub async fn history(&self, context: &Context, years: Option<Vec<i32>>) -> FieldResult<Vec<PollHistoryEntry>> {
+        history_impl(context, VoteSection::CP, self.members().join(","), years).await
+    }
+    /// 票数趋势，默认统计整个投票期间（GMT+8）每小时的票数
+    pub async fn trends(&self, context: &Context, bucket: Option<TrendBucket>, utc_offset_minutes: Option<i32>, from_date: Option<DateTime<Utc>>, to_date: Option<DateTime<Utc>>, filter: Option<FilterConditions>) -> FieldResult<Trends> {
+        trends_impl(context, VoteSection::CP, self.members().join(","), bucket, utc_offset_minutes, from_date, to_date, filter.or_else(|| self.filter.clone())).await
+    }
+    /// 同投率：投了该CP的人中也投了目标的比例，目标项目默认与来源相同
+    pub async fn cooccurrence_ratio(&self, context: &Context, target_id: String, target_section: Option<VoteSection>) -> FieldResult<Option<f64>> {
+        let matrix = cooccurrence_impl(context, VoteSection::CP, vec![self.members().join(",")], target_section, Some(vec![target_id]), Some(1), self.filter.clone()).await?;
+        Ok(matrix.entries.first().map(|x| x.ratio))
+    }
+}
+
+#[juniper::graphql_object(Context = Context, description = "CP的结果")]
+impl CPRankResult {
+    /// 所有CP结果
+    pub fn cps(&self) -> &Vec<SingleCPResult> {
+        &self.cps
+    }
+    /// 使用的过滤器
+    pub fn filter_condtions(&self) -> Option<FilterConditionsOutput> {
+        self.filter_condtions.clone()
+    }
+}
+
+// ------------------------------------------------
+// Root Quries
+// ------------------------------------------------
+
+use crate::services::*;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RankQuery {
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SingleResultQuery {
+    pub id: String,
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryQuery {
+    pub section: VoteSection,
+    /// 人物/音乐ID，CP为逗号分隔的成员ID
+    pub id: String,
+    /// 为空时返回所有届次
+    pub years: Option<Vec<i32>>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SingleCPQuery {
+    /// 排序后的CP成员
+    pub members: Vec<String>,
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrendsQuery {
+    pub section: VoteSection,
+    /// 人物/音乐ID，CP为逗号分隔的成员ID
+    pub id: String,
+    pub bucket: TrendBucket,
+    pub utc_offset_minutes: i32,
+    /// 按粒度对齐后的开始时间
+    pub from_date: DateTime<Utc>,
+    /// 按粒度对齐后的结束时间
+    pub to_date: DateTime<Utc>,
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CooccurrenceQuery {
+    pub section: VoteSection,
+    pub ids: Vec<String>,
+    pub target_section: VoteSection,
+    /// 为空时不限制目标
+    pub target_ids: Option<Vec<String>>,
+    pub top_k: i32,
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReasonsQuery {
+    pub section: VoteSection,
+    pub id: String,
+    pub limit: i32,
+    pub after: Option<String>,
+    pub order_by: ReasonOrder,
+    pub search: Option<String>,
+    pub first_only: bool
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PaperResultsQuery {
+    /// 为空时返回所有问卷项目
+    pub question_ids: Option<Vec<String>>,
+    pub text_sample_size: i32,
+    pub filter: Option<FilterConditions>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CharacterReasonsQuery {
+    pub id: String
+}
+
+pub async fn character_reasons_impl(context: &Context, id: String) -> FieldResult<Reasons> {
+    let id = normalize_entry_id(VoteSection::Character, &id).map_err(|e| gateway_error("INVALID_RESULT_QUERY", &e))?;
+    let query_json = CharacterReasonsQuery { id: id };
+    let result: Reasons = request_upstream(format!("{}/v1/character-reasons/", RESULT_QUERY()), query_json).await?;
+    Ok(result)
+}
+
+pub async fn single_character_result_impl(context: &Context, id: String, filter: Option<FilterConditions>) -> FieldResult<SingleCharacterResult> {
+    let id = normalize_entry_id(VoteSection::Character, &id).map_err(|e| gateway_error("INVALID_RESULT_QUERY", &e))?;
+    let filter = normalize_filter(filter)?;
+    let query_json = SingleResultQuery { id: id, filter: filter.clone() };
+    let mut result: SingleCharacterResult = request_upstream(format!("{}/v1/single-character/", RESULT_QUERY()), query_json).await?;
+    result.filter = filter;
+    Ok(result)
+}
+
+pub async fn character_rank_result_impl(context: &Context, filter: Option<FilterConditions>) -> FieldResult<CharacterRankResult> {
+    let filter = normalize_filter(filter)?;
+    let query_json = RankQuery { filter: filter.clone() };
+    let mut result: CharacterRankResult = request_upstream(format!("{}/v1/character-rank/", RESULT_QUERY()), query_json).await?;
+    result.characters.iter_mut().for_each(|x| x.filter = filter.clone());
+    result.filter_condtions = filter.as_ref().map(FilterConditionsOutput::from_input);
+    Ok(result)
+}
+
+pub async fn single_music_result_impl(context: &Context, id: String, filter: Option<FilterConditions>) -> FieldResult<SingleMusicResult> {
+    let id = normalize_entry_id(VoteSection::Music, &id).map_err(|e| gateway_error("INVALID_RESULT_QUERY", &e))?;
+    let filter = normalize_filter(filter)?;
+    let query_json = SingleResultQuery { id: id, filter: filter.clone() };
+    let mut result: SingleMusicResult = request_upstream(format!("{}/v1/single-music/", RESULT_QUERY()), query_json).await?;
+    result.filter = filter;
+    Ok(result)
+}
+
+pub async fn music_rank_result_impl(context: &Context, filter: Option<FilterConditions>) -> FieldResult<MusicRankResult> {
+    let filter = normalize_filter(filter)?;
+    let query_json = RankQuery { filter: filter.clone() };
+    let mut result: MusicRankResult = request_upstream(format!("{}/v1/music-rank/", RESULT_QUERY()), query_json).await?;
+    result.musics.iter_mut().for_each(|x| x.filter = filter.clone());
+    result.filter_condtions = filter.as_ref().map(FilterConditionsOutput::from_input);
+    Ok(result)
+}
+
+pub async fn single_cp_result_impl(context: &Context, id_a: String, id_b: String, id_c: Option<String>, filter: Option<FilterConditions>) -> FieldResult<SingleCPResult> {
+    let members = canonical_cp_members(&id_a, &id_b, id_c.as_deref()).join(",");
+    let members = normalize_entry_id(VoteSection::CP, &members).map_err(|e| gateway_error("INVALID_RESULT_QUERY", &e))?;
+    let filter = normalize_filter(filter)?;
+    let query_json = SingleCPQuery {
+        members: members.split(',').map(|x| x.to_string()).collect(),
+        filter: filter.clone()
+    };
+    let mut result: SingleCPResult = request_upstream(format!("{}/v1/single-cp/", RESULT_QUERY()), query_json).await?;
+    result.canonicalize();
+    result.filter = filter;
+    Ok(result)
+}
+
+pub async fn cp_rank_result_impl(context: &Context, filter: Option<FilterConditions>) -> FieldResult<CPRankResult> {
+    let filter = normalize_filter(filter)?;
+    let query_json = RankQuery { filter: filter.clone() };
+    let mut result: CPRankResult = request_upstream(format!("{}/v1/cp-rank/", RESULT_QUERY()), query_json).await?;
+    result.cps.iter_mut().for_each(|x| {
+        x.canonicalize();
+        x.filter = filter.clone();
+    });
+    result.filter_condtions = filter.as_ref().map(FilterConditionsOutput::from_input);
+    Ok(result)
+}
+
+/// 单次最多查询的往届届次数
+const MAX_HISTORY_YEARS: usize = 32;
+
+pub async fn history_impl(context: &Context, section: VoteSection, id: String, years: Option<Vec<i32>>) -> FieldResult<Vec<PollHistoryEntry>> {
+    if section != VoteSection::Character && section != VoteSection::Music && section != VoteSection::CP {
+        return Err(gateway_error("INVALID_HISTORY_QUERY", &format!("{:?} has no history", section)));
+    }
+    let id = normalize_entry_id(section, &id).map_err(|e| gateway_error("INVALID_HISTORY_QUERY", &e))?;
+    let years = match years {
+        Some(mut years) => {
+            years.sort();
+            years.dedup();
+            if years.len() > MAX_HISTORY_YEARS {
+                return Err(gateway_error("INVALID_HISTORY_QUERY", &format!("at most {} years can be queried", MAX_HISTORY_YEARS)));
+            }
+            Some(years)
+        },
+        None => None
+    };
+    let query_json = HistoryQuery { section: section, id: id, years: years };
+    let mut result: Vec<PollHistoryEntry> = request_upstream(format!("{}/v1/history/", RESULT_QUERY()), query_json).await?;
+    result.sort_by_key(|x| x.year);
+    Ok(result)
+}
+
+/// 单次趋势查询最多返回的时间段数
+const MAX_TREND_BUCKETS: i64 = 24 * 62;
+
+fn bucket_duration(bucket: TrendBucket) -> Duration {
+    match bucket {
+        TrendBucket::Hour => Duration::hours(1),
+        TrendBucket::Day => Duration::days(1)
+    }
+}
+
+/// 在给定时区内向下对齐到整点/零点
+fn bucket_floor(t: DateTime<Utc>, bucket: TrendBucket, tz: &FixedOffset) -> DateTime<Utc> {
+    let local = t.with_timezone(tz).naive_local();
+    let floored = match bucket {
+        TrendBucket::Hour => local.date().and_hms(local.hour(), 0, 0),
+        TrendBucket::Day => local.date().and_hms(0, 0, 0)
+    };
+    tz.from_local_datetime(&floored).unwrap().with_timezone(&Utc)
+}
+
+fn bucket_ceil(t: DateTime<Utc>, bucket: TrendBucket, tz: &FixedOffset) -> DateTime<Utc> {
+    let floored = bucket_floor(t, bucket, tz);
+    if floored == t { t } else { floored + bucket_duration(bucket) }
+}
+
+pub async fn trends_impl(context: &Context, section: VoteSection, id: String, bucket: Option<TrendBucket>, utc_offset_minutes: Option<i32>, from_date: Option<DateTime<Utc>>, to_date: Option<DateTime<Utc>>, filter: Option<FilterConditions>) -> FieldResult<Trends> {
+    let filter = normalize_filter(filter)?;
+    let bucket = bucket.unwrap_or(TrendBucket::Hour);
+    let utc_offset_minutes = utc_offset_minutes.unwrap_or(VOTE_UTC_OFFSET_MINUTES);
+    if !(-14 * 60..=14 * 60).contains(&utc_offset_minutes) {
+        return Err(gateway_error("INVALID_TREND_RANGE", &format!("utc offset {} minutes is out of range", utc_offset_minutes)));
+    }
+    let tz = FixedOffset::east_opt(utc_offset_minutes * 60).unwrap();
+    let from_date = bucket_floor(from_date.unwrap_or_else(vote_start), bucket, &tz);
+    let to_date = bucket_ceil(to_date.unwrap_or_else(vote_end), bucket, &tz);
+    if from_date >= to_date {
+        return Err(gateway_error("INVALID_TREND_RANGE", "from_date must be earlier than to_date"));
+    }
+    if (to_date - from_date).num_seconds() / bucket_duration(bucket).num_seconds() > MAX_TREND_BUCKETS {
+        return Err(gateway_error("INVALID_TREND_RANGE", &format!("trend range exceeds {} buckets", MAX_TREND_BUCKETS)));
+    }
+    let query_json = TrendsQuery {
+        section: section,
+        id: id,
+        bucket: bucket,
+        utc_offset_minutes: utc_offset_minutes,
+        from_date: from_date,
+        to_date: to_date,
+        filter: filter
+    };
+    let mut result: Trends = request_upstream(format!("{}/v1/trends/", RESULT_QUERY()), query_json).await?;
+    result.fill_cumulative();
+    Ok(result)
+}
+
+/// 单次同投查询最多的来源数
+const MAX_COOCCURRENCE_SOURCES: usize = 100;
+/// 每个来源最多保留的目标数
+const MAX_COOCCURRENCE_TOP_K: i32 = 200;
+
+fn normalize_cooccurrence_ids(section: VoteSection, ids: &[String]) -> FieldResult<Vec<String>> {
+    if section == VoteSection::Paper {
+        return Err(gateway_error("INVALID_COOCCURRENCE", "cooccurrence is not available for paper"));
+    }
+    let mut normalized = ids.iter()
+        .map(|id| normalize_entry_id(section, id))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| gateway_error("INVALID_COOCCURRENCE", &e))?;
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
+pub async fn cooccurrence_impl(context: &Context, section: VoteSection, ids: Vec<String>, target_section: Option<VoteSection>, target_ids: Option<Vec<String>>, top_k: Option<i32>, filter: Option<FilterConditions>) -> FieldResult<CooccurrenceMatrix> {
+    let filter = normalize_filter(filter)?;
+    let target_section = target_section.unwrap_or(section);
+    let top_k = top_k.unwrap_or(10);
+    if top_k < 1 || top_k > MAX_COOCCURRENCE_TOP_K {
+        return Err(gateway_error("INVALID_COOCCURRENCE", &format!("topK must be between 1 and {}", MAX_COOCCURRENCE_TOP_K)));
+    }
+    let ids = normalize_cooccurrence_ids(section, &ids)?;
+    if ids.is_empty() || ids.len() > MAX_COOCCURRENCE_SOURCES {
+        return Err(gateway_error("INVALID_COOCCURRENCE", &format!("ids must contain between 1 and {} entries", MAX_COOCCURRENCE_SOURCES)));
+    }
+    let target_ids = match target_ids {
+        Some(target_ids) => Some(normalize_cooccurrence_ids(target_section, &target_ids)?),
+        None => None
+    };
+    let query_json = CooccurrenceQuery {
+        section: section,
+        ids: ids,
+        target_section: target_section,
+        target_ids: target_ids,
+        top_k: top_k,
+        filter: filter.clone()
+    };
+    let mut result: CooccurrenceMatrix = request_upstream(format!("{}/v1/cooccurrence/", RESULT_QUERY()), query_json).await?;
+    // 同一项目内自己和自己的同投没有意义
+    if section == target_section {
+        result.entries.retain(|x| x.source != x.target);
+    }
+    result.entries.sort_by(|a, b| a.source.cmp(&b.source).then(b.count.cmp(&a.count)));
+    let mut kept_per_source = 0;
+    let mut last_source: Option<String> = None;
+    result.entries.retain(|x| {
+        if last_source.as_ref() != Some(&x.source) {
+            last_source = Some(x.source.clone());
+            kept_per_source = 0;
+        }
+        kept_per_source += 1;
+        kept_per_source <= top_k
+    });
+    result.filter_condtions = filter.as_ref().map(FilterConditionsOutput::from_input);
+    Ok(result)
+}
+
+/// 每页默认的理由数
+const DEFAULT_REASONS_PAGE_SIZE: i32 = 20;
+/// 每页最多的理由数
+const MAX_REASONS_PAGE_SIZE: i32 = 100;
+/// 搜索关键词最大长度
+const MAX_REASONS_SEARCH_LENGTH: usize = 50;
+
+pub async fn reasons_impl(context: &Context, section: VoteSection, id: String, first: Option<i32>, after: Option<String>, order_by: Option<ReasonOrder>, search: Option<String>, first_only: Option<bool>) -> FieldResult<ReasonConnection> {
+    // 只有人物和音乐有投票理由
+    if section != VoteSection::Character && section != VoteSection::Music {
+        return Err(gateway_error("INVALID_REASONS_QUERY", &format!("{:?} has no voting reasons", section)));
+    }
+    let id = normalize_entry_id(section, &id).map_err(|e| gateway_error("INVALID_REASONS_QUERY", &e))?;
+    let limit = first.unwrap_or(DEFAULT_REASONS_PAGE_SIZE);
+    if limit < 1 || limit > MAX_REASONS_PAGE_SIZE {
+        return Err(gateway_error("INVALID_REASONS_QUERY", &format!("first must be between 1 and {}", MAX_REASONS_PAGE_SIZE)));
+    }
+    let search = search.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    if let Some(search) = &search {
+        if search.chars().count() > MAX_REASONS_SEARCH_LENGTH {
+            return Err(gateway_error("INVALID_REASONS_QUERY", &format!("search must not exceed {} characters", MAX_REASONS_SEARCH_LENGTH)));
+        }
+    }
+    let query_json = ReasonsQuery {
+        section: section,
+        id: id,
+        limit: limit + 1,
+        after: after.clone(),
+        order_by: order_by.unwrap_or(ReasonOrder::Newest),
+        search: search,
+        first_only: first_only.unwrap_or(false)
+    };
+    let mut page: ReasonsPageRest = request_upstream(format!("{}/v1/reasons/", RESULT_QUERY()), query_json).await?;
+    let has_next_page = page.edges.len() > limit as usize;
+    page.edges.truncate(limit as usize);
+    Ok(ReasonConnection {
+        page_info: PageInfo {
+            has_next_page: has_next_page,
+            has_previous_page: after.is_some(),
+            start_cursor: page.edges.first().map(|x| x.cursor.clone()),
+            end_cursor: page.edges.last().map(|x| x.cursor.clone())
+        },
+        edges: page.edges,
+        total_count: page.total_count
+    })
+}
+
+/// 默认每个文本问题抽样的回答数
+const DEFAULT_PAPER_TEXT_SAMPLE_SIZE: i32 = 20;
+/// 每个文本问题最多抽样的回答数
+const MAX_PAPER_TEXT_SAMPLE_SIZE: i32 = 200;
+
+pub async fn paper_results_impl(context: &Context, filter: Option<FilterConditions>, question_ids: Option<Vec<String>>, text_sample_size: Option<i32>) -> FieldResult<PaperResults> {
+    let filter = normalize_filter(filter)?;
+    let text_sample_size = text_sample_size.unwrap_or(DEFAULT_PAPER_TEXT_SAMPLE_SIZE);
+    if text_sample_size < 0 || text_sample_size > MAX_PAPER_TEXT_SAMPLE_SIZE {
+        return Err(gateway_error("INVALID_PAPER_QUERY", &format!("textSampleSize must be between 0 and {}", MAX_PAPER_TEXT_SAMPLE_SIZE)));
+    }
+    let question_ids = match question_ids {
+        Some(ids) => Some(ids.iter()
+            .map(|id| normalize_entry_id(VoteSection::Paper, id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| gateway_error("INVALID_PAPER_QUERY", &e))?),
+        None => None
+    };
+    let query_json = PaperResultsQuery {
+        question_ids: question_ids,
+        text_sample_size: text_sample_size,
+        filter: filter.clone()
+    };
+    let mut result: PaperResults = request_upstream(format!("{}/v1/paper-results/", RESULT_QUERY()), query_json).await?;
+    for item in result.results.iter_mut() {
+        if let Some(texts) = item.answers_texts.as_mut() {
+            texts.truncate(text_sample_size as usize);
+        }
+    }
+    if let Some(trends) = result.trends.as_mut() {
+        trends.fill_cumulative();
+    }
+    result.filter_condtions = filter.as_ref().map(FilterConditionsOutput::from_input);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cond(question: &str) -> SingleFilterCondition {
+        SingleFilterCondition {
+            section: VoteSection::Paper,
+            condition: FilterConditionOp::Eq,
+            lhs: question.to_string(),
+            rhs: "yes".to_string()
+        }
+    }
+
+    fn filter(conditions: Vec<SingleFilterCondition>) -> FilterConditions {
+        FilterConditions {
+            conditions: if conditions.is_empty() { None } else { Some(conditions) },
+            and: None,
+            or: None,
+            not: None
+        }
+    }
+
+    fn and(children: Vec<FilterConditions>) -> FilterConditions {
+        FilterConditions { and: Some(children), ..filter(vec![]) }
+    }
+
+    fn or(children: Vec<FilterConditions>) -> FilterConditions {
+        FilterConditions { or: Some(children), ..filter(vec![]) }
+    }
+
+    fn not(child: FilterConditions) -> FilterConditions {
+        FilterConditions { not: Some(Box::new(child)), ..filter(vec![]) }
+    }
+
+    fn normalized(f: FilterConditions) -> Option<serde_json::Value> {
+        normalize_filter(Some(f)).unwrap().map(|f| serde_json::to_value(f).unwrap())
+    }
+
+    fn json(f: FilterConditions) -> Option<serde_json::Value> {
+        Some(serde_json::to_value(f).unwrap())
+    }
+
+    fn error(f: FilterConditions) -> String {
+        normalize_filter(Some(f)).unwrap_err().message().to_string()
+    }
+
+    #[test]
+    fn removes_double_negation() {
+        assert_eq!(normalized(not(not(filter(vec![cond("q1")])))), json(filter(vec![cond("q1")])));
+    }
+
+    #[test]
+    fn keeps_single_negation() {
+        assert_eq!(normalized(not(filter(vec![cond("q1")]))), json(not(filter(vec![cond("q1")]))));
+    }
+
+    #[test]
+    fn collapses_single_branch_or() {
+        assert_eq!(normalized(or(vec![filter(vec![cond("q1")])])), json(filter(vec![cond("q1")])));
+    }
+
+    #[test]
+    fn or_with_empty_branch_matches_all() {
+        assert_eq!(normalized(or(vec![filter(vec![cond("q1")]), filter(vec![])])), None);
+        let f = FilterConditions { or: Some(vec![filter(vec![cond("q1")]), filter(vec![])]), ..filter(vec![cond("q2")]) };
+        assert_eq!(normalized(f), json(filter(vec![cond("q2")])));
+    }
+
+    #[test]
+    fn rejects_empty_or() {
+        assert!(error(or(vec![])).contains("at least one filter"));
+    }
+
+    #[test]
+    fn rejects_not_of_empty_filter() {
+        assert!(error(not(filter(vec![]))).contains("exclude every vote"));
+    }
+
+    #[test]
+    fn flattens_nested_and() {
+        let f = and(vec![filter(vec![cond("q3")]), and(vec![filter(vec![cond("q1")]), and(vec![filter(vec![cond("q2")])])])]);
+        assert_eq!(normalized(f), json(filter(vec![cond("q1"), cond("q2"), cond("q3")])));
+    }
+
+    #[test]
+    fn keeps_and_children_with_or_or_not() {
+        let branches = or(vec![filter(vec![cond("q1")]), filter(vec![cond("q2")])]);
+        let f = and(vec![filter(vec![cond("q3")]), branches.clone()]);
+        assert_eq!(normalized(f), json(FilterConditions { and: Some(vec![branches]), ..filter(vec![cond("q3")]) }));
+    }
+
+    #[test]
+    fn sorts_and_dedups_conditions() {
+        assert_eq!(normalized(filter(vec![cond("q2"), cond("q1"), cond("q2")])), json(filter(vec![cond("q1"), cond("q2")])));
+    }
+
+    #[test]
+    fn empty_filter_matches_all() {
+        assert_eq!(normalized(filter(vec![])), None);
+        assert_eq!(normalized(and(vec![filter(vec![]), filter(vec![])])), None);
+    }
+
+    #[test]
+    fn limits_depth() {
+        let mut f = filter(vec![cond("q1")]);
+        for _ in 0..MAX_FILTER_DEPTH {
+            f = and(vec![f]);
+        }
+        assert!(normalize_filter(Some(f.clone())).is_ok());
+        assert!(error(and(vec![f])).contains("nested deeper"));
+    }
+
+    #[test]
+    fn limits_condition_count() {
+        let conditions = |n: usize| (0..n).map(|i| cond(&format!("q{}", i))).collect::<Vec<_>>();
+        assert!(normalize_filter(Some(filter(conditions(MAX_FILTER_CONDITIONS)))).is_ok());
+        assert!(error(filter(conditions(MAX_FILTER_CONDITIONS + 1))).contains("conditions"));
+    }
+
+    #[test]
+    fn limits_empty_sub_filters() {
+        assert!(normalize_filter(Some(and(vec![filter(vec![]); MAX_FILTER_NODES - 1]))).is_ok());
+        assert!(error(and(vec![filter(vec![]); MAX_FILTER_NODES])).contains("sub-filters"));
+    }
+}
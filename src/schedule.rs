@@ -0,0 +1,89 @@
+
+use chrono::{DateTime, FixedOffset, Utc};
+use juniper::FieldResult;
+
+use crate::common::gateway_error;
+use crate::config::{VotingPhase, config};
+use crate::context::Context;
+use crate::result_query::VoteSection;
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[graphql(description="投票项目当前状态")]
+pub enum VotingPhaseState {
+	/// 尚未开始
+	NotStarted,
+	/// 投票中
+	Open,
+	/// 已结束
+	Closed,
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="单个投票项目的开放时间")]
+pub struct VotingPhaseStatus {
+	/// 投票项目
+	pub section: VoteSection,
+	/// 开始时间
+	pub open: DateTime<FixedOffset>,
+	/// 结束时间
+	pub close: DateTime<FixedOffset>,
+	/// 当前状态
+	pub state: VotingPhaseState,
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(description="投票时间表")]
+pub struct VotingSchedule {
+	/// 服务器时间
+	pub server_time: DateTime<Utc>,
+	/// 各项目开放时间
+	pub phases: Vec<VotingPhaseStatus>,
+}
+
+fn phase(section: VoteSection) -> &'static VotingPhase {
+	let schedule = config().schedule();
+	match section {
+		VoteSection::Character => &schedule.character,
+		VoteSection::Music => &schedule.music,
+		VoteSection::CP => &schedule.cp,
+		VoteSection::Paper => &schedule.paper,
+		VoteSection::Work => &schedule.work,
+	}
+}
+
+fn phase_state(phase: &VotingPhase, now: DateTime<Utc>) -> VotingPhaseState {
+	if now < phase.open.with_timezone(&Utc) {
+		VotingPhaseState::NotStarted
+	} else if now >= phase.close.with_timezone(&Utc) {
+		VotingPhaseState::Closed
+	} else {
+		VotingPhaseState::Open
+	}
+}
+
+/// 在每个提交接口开头调用，不在开放时间内时返回 VOTING_NOT_STARTED 或 VOTING_CLOSED
+pub fn check_voting_open(section: VoteSection) -> FieldResult<()> {
+	let phase = phase(section);
+	match phase_state(phase, Utc::now()) {
+		VotingPhaseState::NotStarted => Err(gateway_error("VOTING_NOT_STARTED", &format!("voting for {:?} opens at {}", section, phase.open.to_rfc3339()))),
+		VotingPhaseState::Closed => Err(gateway_error("VOTING_CLOSED", &format!("voting for {:?} closed at {}", section, phase.close.to_rfc3339()))),
+		VotingPhaseState::Open => Ok(()),
+	}
+}
+
+pub async fn votingSchedule_impl(context: &Context) -> FieldResult<VotingSchedule> {
+	let now = Utc::now();
+	let sections = [VoteSection::Character, VoteSection::Music, VoteSection::CP, VoteSection::Paper, VoteSection::Work];
+	Ok(VotingSchedule {
+		server_time: now,
+		phases: sections.iter().map(|section| {
+			let phase = phase(*section);
+			VotingPhaseStatus {
+				section: *section,
+				open: phase.open,
+				close: phase.close,
+				state: phase_state(phase, now),
+			}
+		}).collect(),
+	})
+}
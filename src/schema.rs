@@ -4,6 +4,19 @@ use juniper::RootNode;
 
 use chrono::{DateTime, Utc};
 
+use crate::result_query::CPRankResult;
+use crate::result_query::CharacterRankResult;
+use crate::result_query::CooccurrenceMatrix;
+use crate::result_query::FilterConditions;
+use crate::result_query::MusicRankResult;
+use crate::result_query::PaperResults;
+use crate::result_query::ReasonConnection;
+use crate::result_query::ReasonOrder;
+use crate::result_query::Reasons;
+use crate::result_query::SingleCPResult;
+use crate::result_query::SingleCharacterResult;
+use crate::result_query::SingleMusicResult;
+use crate::result_query::VoteSection;
 use crate::submit_handler::CPSubmitGQL;
 use crate::submit_handler::CPSubmitRestQuery;
 use crate::submit_handler::CharacterSubmitGQL;
@@ -12,13 +25,15 @@ use crate::submit_handler::MusicSubmitGQL;
 use crate::submit_handler::MusicSubmitRestQuery;
 use crate::submit_handler::PaperSubmitGQL;
 use crate::submit_handler::PaperSubmitRestQuery;
+use crate::submit_handler::PaperSubmitTypedGQL;
 use crate::submit_handler::WorkSubmitGQL;
+use crate::submit_handler::WorkSubmitRestQuery;
 use crate::user_manager::EmailLoginInputs;
 use crate::user_manager::EmailLoginInputsForExistingVoters;
 use crate::user_manager::LoginResults;
 use crate::user_manager::PhoneLoginInputs;
 
-use crate::{user_manager, submit_handler, vote_data, result_query};
+use crate::{auth, user_manager, submit_handler, vote_data, result_query, questionnaire, schedule};
 
 use super::context::Context;
 
@@ -48,11 +63,38 @@ impl Query {
 
 	}
 
+	/// 搜索人物，query 匹配名字和别名（含拼音、罗马字），work 为出场作品ID
+	async fn searchVotableCharacters(context: &Context, query: Option<String>, work: Option<String>, first: Option<i32>, after: Option<String>) -> FieldResult<vote_data::VotableCharacterConnection> {
+		vote_data::searchVotableCharacters_impl(context, query, work, first, after).await
+	}
+	/// 搜索音乐，work 为首次出场的作品ID
+	async fn searchVotableMusics(context: &Context, query: Option<String>, work: Option<String>, first: Option<i32>, after: Option<String>) -> FieldResult<vote_data::VotableMusicConnection> {
+		vote_data::searchVotableMusics_impl(context, query, work, first, after).await
+	}
+	/// 搜索作品
+	async fn searchVotableWorks(context: &Context, query: Option<String>, first: Option<i32>, after: Option<String>) -> FieldResult<vote_data::VotableWorkConnection> {
+		vote_data::searchVotableWorks_impl(context, query, first, after).await
+	}
+
+	/// 各项目投票时间及服务器时间
+	async fn votingSchedule(context: &Context) -> FieldResult<schedule::VotingSchedule> {
+		schedule::votingSchedule_impl(context).await
+	}
+
+	// ------------------------------------------------
+	//     questionnaire
+	// ------------------------------------------------
+
+	/// 问卷定义
+	async fn questionnaire(context: &Context) -> FieldResult<questionnaire::Questionnaire> {
+		questionnaire::questionnaire_impl(context).await
+	}
+
 	// ------------------------------------------------
 	//     user management
 	// ------------------------------------------------
-	async fn userTokenStatus(context: &Context, user_token: String, vote_token: Option<String>) -> FieldResult<bool> {
-		user_manager::user_token_status(user_token, vote_token).await
+	async fn userTokenStatus(context: &Context, user_token: Option<String>, vote_token: Option<String>) -> FieldResult<bool> {
+		user_manager::user_token_status(context.require_session_token(user_token)?, vote_token.filter(|t| !t.is_empty()).or_else(|| context.vote_token.clone().filter(|t| auth::has_vote_audience(t)))).await
 	}
 
 	// ------------------------------------------------
@@ -60,24 +102,83 @@ impl Query {
 	// ------------------------------------------------
 	
 	/// Get Character
-	async fn getSubmitCharacterVote(context: &Context, vote_token: String) -> FieldResult<CharacterSubmitRestQuery> {
+	async fn getSubmitCharacterVote(context: &Context, vote_token: Option<String>) -> FieldResult<CharacterSubmitRestQuery> {
 		submit_handler::getSubmitCharacterVote_impl(context, vote_token).await
 	}
 
 	/// Get Music
-	async fn getSubmitMusicVote(context: &Context, vote_token: String) -> FieldResult<MusicSubmitRestQuery> {
+	async fn getSubmitMusicVote(context: &Context, vote_token: Option<String>) -> FieldResult<MusicSubmitRestQuery> {
 		submit_handler::getSubmitMusicVote_impl(context, vote_token).await
 	}
 
+	/// Get Work
+	async fn getSubmitWorkVote(context: &Context, vote_token: Option<String>) -> FieldResult<WorkSubmitRestQuery> {
+		submit_handler::getSubmitWorkVote_impl(context, vote_token).await
+	}
+
 	/// Get CP
-	async fn getSubmitCPVote(context: &Context, vote_token: String) -> FieldResult<CPSubmitRestQuery> {
+	async fn getSubmitCPVote(context: &Context, vote_token: Option<String>) -> FieldResult<CPSubmitRestQuery> {
 		submit_handler::getSubmitCPVote_impl(context, vote_token).await
 	}
 
 	/// Get Paper
-	async fn getSubmitPaperVote(context: &Context, vote_token: String) -> FieldResult<PaperSubmitRestQuery> {
+	async fn getSubmitPaperVote(context: &Context, vote_token: Option<String>) -> FieldResult<PaperSubmitRestQuery> {
 		submit_handler::getSubmitPaperVote_impl(context, vote_token).await
 	}
+
+	// ------------------------------------------------
+	//     result_query
+	// ------------------------------------------------
+
+	/// 人物排名
+	async fn characterRank(context: &Context, filter: Option<FilterConditions>) -> FieldResult<CharacterRankResult> {
+		result_query::character_rank_result_impl(context, filter).await
+	}
+
+	/// 单个人物结果
+	async fn singleCharacterResult(context: &Context, id: String, filter: Option<FilterConditions>) -> FieldResult<SingleCharacterResult> {
+		result_query::single_character_result_impl(context, id, filter).await
+	}
+
+	/// 人物投票理由
+	async fn characterReasons(context: &Context, id: String) -> FieldResult<Reasons> {
+		result_query::character_reasons_impl(context, id).await
+	}
+
+	/// 分页的投票理由，section 只能是人物或音乐，orderBy 默认最新在前
+	async fn reasons(context: &Context, section: VoteSection, id: String, first: Option<i32>, after: Option<String>, order_by: Option<ReasonOrder>, search: Option<String>, first_only: Option<bool>) -> FieldResult<ReasonConnection> {
+		result_query::reasons_impl(context, section, id, first, after, order_by, search, first_only).await
+	}
+
+	/// 音乐排名
+	async fn musicRank(context: &Context, filter: Option<FilterConditions>) -> FieldResult<MusicRankResult> {
+		result_query::music_rank_result_impl(context, filter).await
+	}
+
+	/// 单个音乐结果
+	async fn singleMusicResult(context: &Context, id: String, filter: Option<FilterConditions>) -> FieldResult<SingleMusicResult> {
+		result_query::single_music_result_impl(context, id, filter).await
+	}
+
+	/// CP排名
+	async fn cpRank(context: &Context, filter: Option<FilterConditions>) -> FieldResult<CPRankResult> {
+		result_query::cp_rank_result_impl(context, filter).await
+	}
+
+	/// 单个CP结果，(A,B) 与 (B,A) 视为同一个CP
+	async fn singleCPResult(context: &Context, id_a: String, id_b: String, id_c: Option<String>, filter: Option<FilterConditions>) -> FieldResult<SingleCPResult> {
+		result_query::single_cp_result_impl(context, id_a, id_b, id_c, filter).await
+	}
+
+	/// 同投率矩阵，ids 为来源项目中的ID（CP为逗号分隔的成员ID），targetSection 默认与 section 相同
+	async fn cooccurrence(context: &Context, section: VoteSection, ids: Vec<String>, target_section: Option<VoteSection>, target_ids: Option<Vec<String>>, top_k: Option<i32>, filter: Option<FilterConditions>) -> FieldResult<CooccurrenceMatrix> {
+		result_query::cooccurrence_impl(context, section, ids, target_section, target_ids, top_k, filter).await
+	}
+
+	/// 问卷结果，可按其他项目的投票过滤，questionIds 为空时返回所有项目
+	async fn paperResults(context: &Context, filter: Option<FilterConditions>, question_ids: Option<Vec<String>>, text_sample_size: Option<i32>) -> FieldResult<PaperResults> {
+		result_query::paper_results_impl(context, filter, question_ids, text_sample_size).await
+	}
 }
 
 
@@ -122,27 +223,32 @@ impl Mutation {
 	}
 
 	/// 更新邮箱
-	async fn update_email(context: &Context, user_token: String, email: String, verify_code: String) -> FieldResult<bool> {
+	async fn update_email(context: &Context, user_token: Option<String>, email: String, verify_code: String) -> FieldResult<bool> {
 		user_manager::update_email(context, user_token, email, verify_code).await
 	}
 
 	/// 更新手机
-	async fn update_phone(context: &Context, user_token: String, phone: String, verify_code: String) -> FieldResult<bool> {
+	async fn update_phone(context: &Context, user_token: Option<String>, phone: String, verify_code: String) -> FieldResult<bool> {
 		user_manager::update_phone(context, user_token, phone, verify_code).await
 	}
 
 	/// 更新昵称
-	async fn update_nickname(context: &Context, user_token: String, new_nickname: String) -> FieldResult<bool> {
+	async fn update_nickname(context: &Context, user_token: Option<String>, new_nickname: String) -> FieldResult<bool> {
 		user_manager::update_nickname(context, user_token, new_nickname).await
 	}
 
 	/// 更新密码
-	async fn update_password(context: &Context, user_token: String, old_password: Option<String>, new_password: String) -> FieldResult<bool> {
+	async fn update_password(context: &Context, user_token: Option<String>, old_password: Option<String>, new_password: String) -> FieldResult<bool> {
 		user_manager::update_password(context, user_token, old_password, new_password).await
 	}
 
+	/// 退出登录，清除token cookie
+	async fn logout(context: &Context, user_token: Option<String>) -> FieldResult<bool> {
+		user_manager::logout(context, user_token).await
+	}
+
 	/// 账号注销
-	async fn remove_voter(context: &Context, user_token: String, old_password: Option<String>) -> FieldResult<bool> {
+	async fn remove_voter(context: &Context, user_token: Option<String>, old_password: Option<String>) -> FieldResult<bool> {
 		user_manager::remove_voter(context, user_token, old_password).await
 	}
 
@@ -160,6 +266,11 @@ impl Mutation {
 	   submit_handler::submitMusicVote_impl(context, &content).await
 	}
 	
+	/// work
+	async fn submitWorkVote(context: &Context, content: WorkSubmitGQL) -> FieldResult<bool> {
+		submit_handler::submitWorkVote_impl(context, &content).await
+	}
+
 	/// CP
 	async fn submitCPVote(context: &Context, content: CPSubmitGQL) -> FieldResult<bool> {
 		submit_handler::submitCPVote_impl(context, &content).await
@@ -169,6 +280,11 @@ impl Mutation {
 	async fn submitPaperVote(context: &Context, content: PaperSubmitGQL) -> FieldResult<bool> {
 		submit_handler::submitPaperVote_impl(context, &content).await
 	}
+
+	/// paper, typed answers
+	async fn submitPaperVoteTyped(context: &Context, content: PaperSubmitTypedGQL) -> FieldResult<bool> {
+		submit_handler::submitPaperVoteTyped_impl(context, &content).await
+	}
 }
 
 pub struct Subscription;
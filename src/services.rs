@@ -1,17 +1,31 @@
 
-#[cfg(debug_assertions)]
-pub const USER_MANAGER: &'static str = "127.0.0.1:1100";
-#[cfg(debug_assertions)]
-pub const SUBMIT_HANDLER: &'static str = "127.0.0.1:1101";
-#[cfg(debug_assertions)]
-pub const RESULT_QUERY: &'static str = "127.0.0.1:1102";
-#[cfg(debug_assertions)]
-pub const SUBMIT_VALIDATOR: &'static str = "127.0.0.1:1103";
+use juniper::FieldResult;
+use pvrustlib::json_request_gateway;
+use serde::{Serialize, de::DeserializeOwned};
 
-#[cfg(not(debug_assertions))]
-pub const USER_MANAGER: &'static str = "user-manager";
-#[cfg(not(debug_assertions))]
-pub const SUBMIT_HANDLER: &'static str = "submit-handler";
-#[cfg(not(debug_assertions))]
-pub const RESULT_QUERY: &'static str = "result-query";
+use crate::common::{SERVICE_NAME, gateway_error};
+use crate::config::config;
 
+pub fn USER_MANAGER() -> &'static str {
+	config().services.user_manager.trim_end_matches('/')
+}
+
+pub fn SUBMIT_HANDLER() -> &'static str {
+	config().services.submit_handler.trim_end_matches('/')
+}
+
+pub fn RESULT_QUERY() -> &'static str {
+	config().services.result_query.trim_end_matches('/')
+}
+
+pub fn SUBMIT_VALIDATOR() -> &'static str {
+	config().services.submit_validator.trim_end_matches('/')
+}
+
+/// 请求上游服务，超过配置的超时时间后返回 UPSTREAM_TIMEOUT
+pub async fn request_upstream<T: Serialize, R: DeserializeOwned>(url: String, payload: T) -> FieldResult<R> {
+	match tokio::time::timeout(config().upstream_timeout(), json_request_gateway(SERVICE_NAME, &url, payload)).await {
+		Ok(result) => Ok(result?),
+		Err(_) => Err(gateway_error("UPSTREAM_TIMEOUT", &format!("request to {} timed out", url)))
+	}
+}
@@ -1,16 +1,15 @@
 
-use juniper::IntoFieldError;
 use juniper::graphql_value;
 
 use juniper::FieldResult;
 use pvrustlib::EmptyJSON;
-use pvrustlib::ServiceError;
-use pvrustlib::json_request_gateway;
 
-use crate::common::SERVICE_NAME;
-use crate::common::VoteTokenClaim;
 use crate::context::Context;
-use jwt_simple::{prelude::*, algorithms::ECDSAP256kPublicKeyLike};
+use crate::ballot::{canonicalize_cps, validate_characters, validate_musics, validate_works};
+use crate::common::{ValidationIssue, validation_error};
+use crate::result_query::VoteSection;
+use crate::schedule::check_voting_open;
+use crate::questionnaire::{QuestionKind, questionnaire, validate_paper_json};
 
 use bson::DateTime;
 use serde_derive::{Serialize, Deserialize};
@@ -61,6 +60,11 @@ pub struct WorkSubmitRest {
 	pub meta: SubmitMetadata
 }
 
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+pub struct WorkSubmitRestQuery {
+	pub works: Vec<WorkSubmitQuery>
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct CPSubmitRest {
 	pub cps: Vec<CPSubmit>,
@@ -78,11 +82,31 @@ pub struct PaperSubmitRest {
 	pub meta: SubmitMetadata
 }
 
-#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PaperSubmitRestQuery {
 	pub papers_json: String,
 }
 
+#[juniper::graphql_object(Context = Context)]
+impl PaperSubmitRestQuery {
+	/// 问卷的JSON字符串
+	fn papers_json(&self) -> &str {
+		&self.papers_json
+	}
+	/// 解析后的回答，按问卷定义的顺序排列
+	fn answers(&self) -> Vec<PaperSubmitQuery> {
+		let answers = match serde_json::from_str::<serde_json::Value>(&self.papers_json) {
+			Ok(serde_json::Value::Object(answers)) => answers,
+			_ => return vec![]
+		};
+		let mut result: Vec<PaperSubmitQuery> = answers.iter().map(|(id, value)| PaperSubmitQuery::from_json(id, value)).collect();
+		if let Some(q) = questionnaire() {
+			result.sort_by_key(|x| q.questions.iter().position(|y| y.id == x.id).unwrap_or(usize::MAX));
+		}
+		result
+	}
+}
+
 // ------------------------------------------------
 // GQL Schemas
 // ------------------------------------------------
@@ -112,7 +136,8 @@ pub struct CharacterSubmitQuery {
 #[derive(juniper::GraphQLInputObject, Clone)]
 #[graphql(description="Character submit")]
 pub struct CharacterSubmitGQL {
-	pub vote_token: String,
+	/// 投票token，未提供时使用 Authorization 头或 vote_token cookie
+	pub vote_token: Option<String>,
 	pub characters: Vec<CharacterSubmit>
 }
 
@@ -146,10 +171,21 @@ pub struct CPSubmitQuery {
 	pub first: Option<bool>,
 }
 
+/// CP成员按ID排序，使 (A,B) 与 (B,A) 对应同一个CP
+pub fn canonical_cp_members(id_a: &str, id_b: &str, id_c: Option<&str>) -> Vec<String> {
+	let mut members = vec![id_a.to_string(), id_b.to_string()];
+	if let Some(id_c) = id_c {
+		members.push(id_c.to_string());
+	}
+	members.sort();
+	members
+}
+
 #[derive(juniper::GraphQLInputObject, Clone)]
 #[graphql(description="CP submit")]
 pub struct CPSubmitGQL {
-	pub vote_token: String,
+	/// 投票token，未提供时使用 Authorization 头或 vote_token cookie
+	pub vote_token: Option<String>,
 	pub cps: Vec<CPSubmit>
 }
 
@@ -177,7 +213,8 @@ pub struct MusicSubmitQuery {
 #[derive(juniper::GraphQLInputObject, Clone)]
 #[graphql(description="Music submit")]
 pub struct MusicSubmitGQL {
-	pub vote_token: String,
+	/// 投票token，未提供时使用 Authorization 头或 vote_token cookie
+	pub vote_token: Option<String>,
 	pub musics: Vec<MusicSubmit>
 }
 
@@ -190,41 +227,112 @@ pub struct WorkSubmit {
 	pub reason: Option<String>
 }
 
+#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(description="Single work submit")]
+pub struct WorkSubmitQuery {
+	/// 作品ID
+	pub id: String,
+	/// 理由
+	pub reason: Option<String>
+}
+
 #[derive(juniper::GraphQLInputObject, Clone)]
 #[graphql(description="Work submit")]
 pub struct WorkSubmitGQL {
-	pub vote_token: String,
-	pub work: Vec<WorkSubmit>
+	/// 投票token，未提供时使用 Authorization 头或 vote_token cookie
+	pub vote_token: Option<String>,
+	pub works: Vec<WorkSubmit>
 }
 
 #[derive(juniper::GraphQLInputObject, Clone, Serialize, Deserialize)]
-#[graphql(description="Single paper submit")]
+#[graphql(name="PaperAnswerInput", description="Single paper answer, exactly one of choice, choices, text and number must be set")]
 pub struct PaperSubmit {
 	/// 提问ID
 	pub id: String,
-	/// 答案
-	pub answer: String
+	/// 单选答案
+	pub choice: Option<String>,
+	/// 多选答案
+	pub choices: Option<Vec<String>>,
+	/// 文本答案
+	pub text: Option<String>,
+	/// 数值答案
+	pub number: Option<f64>
 }
 
 #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
-#[graphql(description="Single paper submit")]
+#[graphql(name="PaperAnswer", description="Single paper answer")]
 pub struct PaperSubmitQuery {
 	/// 提问ID
 	pub id: String,
-	/// 答案
-	pub answer: String
+	/// 单选答案
+	pub choice: Option<String>,
+	/// 多选答案
+	pub choices: Option<Vec<String>>,
+	/// 文本答案
+	pub text: Option<String>,
+	/// 数值答案
+	pub number: Option<f64>
+}
+
+impl PaperSubmit {
+	/// 转换成 paper_json 中的回答
+	fn to_json(&self) -> Result<serde_json::Value, ValidationIssue> {
+		let issue = |reason: &'static str, message: &str| ValidationIssue::new(self.id.as_str(), reason, message);
+		let value = match (&self.choice, &self.choices, &self.text, self.number) {
+			(Some(choice), None, None, None) => (QuestionKind::SingleChoice, serde_json::Value::from(choice.clone())),
+			(None, Some(choices), None, None) => (QuestionKind::MultiChoice, serde_json::Value::from(choices.clone())),
+			(None, None, Some(text), None) => (QuestionKind::Text, serde_json::Value::from(text.clone())),
+			(None, None, None, Some(number)) => match serde_json::Number::from_f64(number) {
+				Some(number) => (QuestionKind::Number, serde_json::Value::Number(number)),
+				None => return Err(issue("WRONG_TYPE", "number must be finite"))
+			},
+			_ => return Err(issue("WRONG_TYPE", "exactly one of choice, choices, text and number must be set"))
+		};
+		match question_kind(&self.id) {
+			Some(kind) if kind != value.0 => Err(issue("WRONG_TYPE", "answer type does not match the question")),
+			_ => Ok(value.1)
+		}
+	}
 }
 
+impl PaperSubmitQuery {
+	/// 从 paper_json 中的回答还原，字符串按问卷定义区分单选和文本
+	fn from_json(id: &str, value: &serde_json::Value) -> PaperSubmitQuery {
+		let mut answer = PaperSubmitQuery { id: id.to_string(), choice: None, choices: None, text: None, number: None };
+		match value {
+			serde_json::Value::String(s) if question_kind(id) == Some(QuestionKind::SingleChoice) => answer.choice = Some(s.clone()),
+			serde_json::Value::String(s) => answer.text = Some(s.clone()),
+			serde_json::Value::Array(items) => answer.choices = Some(items.iter().map(|x| x.as_str().map(|x| x.to_string()).unwrap_or_else(|| x.to_string())).collect()),
+			serde_json::Value::Number(n) => answer.number = n.as_f64(),
+			serde_json::Value::Null => {},
+			other => answer.text = Some(other.to_string())
+		}
+		answer
+	}
+}
+
+fn question_kind(id: &str) -> Option<QuestionKind> {
+	questionnaire().and_then(|q| q.question(id)).map(|q| q.kind)
+}
 
 #[derive(juniper::GraphQLInputObject, Clone)]
 #[graphql(description="Paper submit")]
 pub struct PaperSubmitGQL {
-	/// 投票token
-	pub vote_token: String,
+	/// 投票token，未提供时使用 Authorization 头或 vote_token cookie
+	pub vote_token: Option<String>,
 	/// 问卷的JSON字符串
 	pub paper_json: String
 }
 
+#[derive(juniper::GraphQLInputObject, Clone)]
+#[graphql(description="Typed paper submit")]
+pub struct PaperSubmitTypedGQL {
+	/// 投票token，未提供时使用 Authorization 头或 vote_token cookie
+	pub vote_token: Option<String>,
+	/// 所有回答
+	pub answers: Vec<PaperSubmit>
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct QuerySubmitRest {
 	pub vote_id: String,
@@ -241,6 +349,9 @@ pub struct VotingStatus {
 	pub cps: bool,
 	/// 问卷是否提交
 	pub papers: bool,
+	/// 作品是否完成
+	#[serde(default)]
+	pub works: bool,
 }
 
 pub fn generate_submit_metadata(vote_id: &str, context: &Context) -> SubmitMetadata {
@@ -248,7 +359,7 @@ pub fn generate_submit_metadata(vote_id: &str, context: &Context) -> SubmitMetad
 		vote_id: vote_id.to_string(),
 		created_at: DateTime::now(),
 		user_ip: context.user_ip.clone(),
-		additional_fingreprint: None, // TODO
+		additional_fingreprint: context.additional_fingureprint.clone(),
 	}
 }
 
@@ -259,142 +370,139 @@ pub fn generate_submit_metadata(vote_id: &str, context: &Context) -> SubmitMetad
 use crate::services::*;
 
 pub async fn submitCharacterVote_impl(context: &Context, content: &CharacterSubmitGQL) -> FieldResult<bool> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&content.vote_token, Some(options));
-	println!("{:?}", result);
-	if let Ok(claim) = result {
-		let submit_json = CharacterSubmitRest {
-			meta: generate_submit_metadata(&claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?, context),
-			characters: content.characters.clone(),
-		};
-		
-		let post_result: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/character/", SUBMIT_HANDLER), submit_json).await?;
-		Ok(true)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
-	}
+	let voter = context.require_voter(content.vote_token.as_deref())?;
+	check_voting_open(VoteSection::Character)?;
+	validate_characters(&content.characters)?;
+	let submit_json = CharacterSubmitRest {
+		meta: generate_submit_metadata(&voter.vote_id, context),
+		characters: content.characters.clone(),
+	};
+	let post_result: EmptyJSON = request_upstream(format!("{}/v1/character/", SUBMIT_HANDLER()), submit_json).await?;
+	Ok(true)
 }
 
 pub async fn submitMusicVote_impl(context: &Context, content: &MusicSubmitGQL) -> FieldResult<bool> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&content.vote_token, Some(options));
-	if let Ok(claim) = result {
-		let submit_json = MusicSubmitRest {
-			meta: generate_submit_metadata(&claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?, context),
-			music: content.musics.clone(),
-		};
-		let post_result: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/music/", SUBMIT_HANDLER), submit_json).await?;
-		Ok(true)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
-	}
+	let voter = context.require_voter(content.vote_token.as_deref())?;
+	check_voting_open(VoteSection::Music)?;
+	validate_musics(&content.musics)?;
+	let submit_json = MusicSubmitRest {
+		meta: generate_submit_metadata(&voter.vote_id, context),
+		music: content.musics.clone(),
+	};
+	let post_result: EmptyJSON = request_upstream(format!("{}/v1/music/", SUBMIT_HANDLER()), submit_json).await?;
+	Ok(true)
+}
+
+pub async fn submitWorkVote_impl(context: &Context, content: &WorkSubmitGQL) -> FieldResult<bool> {
+	let voter = context.require_voter(content.vote_token.as_deref())?;
+	check_voting_open(VoteSection::Work)?;
+	validate_works(&content.works)?;
+	let submit_json = WorkSubmitRest {
+		meta: generate_submit_metadata(&voter.vote_id, context),
+		works: content.works.clone(),
+	};
+	let post_result: EmptyJSON = request_upstream(format!("{}/v1/work/", SUBMIT_HANDLER()), submit_json).await?;
+	Ok(true)
 }
 
 pub async fn submitCPVote_impl(context: &Context, content: &CPSubmitGQL) -> FieldResult<bool> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&content.vote_token, Some(options));
-	if let Ok(claim) = result {
-		let submit_json = CPSubmitRest {
-			meta: generate_submit_metadata(&claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?, context),
-			cps: content.cps.clone(),
-		};
-		let post_result: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/cp/", SUBMIT_HANDLER), submit_json).await?;
-		Ok(true)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
-	}
+	let voter = context.require_voter(content.vote_token.as_deref())?;
+	check_voting_open(VoteSection::CP)?;
+	let cps = canonicalize_cps(&content.cps)?;
+	let submit_json = CPSubmitRest {
+		meta: generate_submit_metadata(&voter.vote_id, context),
+		cps: cps,
+	};
+	let post_result: EmptyJSON = request_upstream(format!("{}/v1/cp/", SUBMIT_HANDLER()), submit_json).await?;
+	Ok(true)
 }
 
 pub async fn submitPaperVote_impl(context: &Context, content: &PaperSubmitGQL) -> FieldResult<bool> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&content.vote_token, Some(options));
-	if let Ok(claim) = result {
-		let submit_json = PaperSubmitRest {
-			meta: generate_submit_metadata(&claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?, context),
-			papers_json: content.paper_json.clone()
-		};
-		let post_result: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/paper/", SUBMIT_HANDLER), submit_json).await?;
-		Ok(true)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
-	}
-}
-
-pub async fn getSubmitCharacterVote_impl(context: &Context, vote_token: String) -> FieldResult<CharacterSubmitRestQuery> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&vote_token, Some(options));
-	if let Ok(claim) = result {
-		let query_json = QuerySubmitRest {
-			vote_id: claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?
-		};
-		let post_result: CharacterSubmitRestQuery = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/get-character/", SUBMIT_HANDLER), query_json).await?;
-		Ok(post_result)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
-	}
-}
-
-pub async fn getSubmitMusicVote_impl(context: &Context, vote_token: String) -> FieldResult<MusicSubmitRestQuery> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&vote_token, Some(options));
-	if let Ok(claim) = result {
-		let query_json = QuerySubmitRest {
-			vote_id: claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?
-		};
-		let post_result: MusicSubmitRestQuery = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/get-music/", SUBMIT_HANDLER), query_json).await?;
-		Ok(post_result)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
-	}
-}
-
-pub async fn getSubmitCPVote_impl(context: &Context, vote_token: String) -> FieldResult<CPSubmitRestQuery> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&vote_token, Some(options));
-	if let Ok(claim) = result {
-		let query_json = QuerySubmitRest {
-			vote_id: claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?
-		};
-		let post_result: CPSubmitRestQuery = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/get-cp/", SUBMIT_HANDLER), query_json).await?;
-		Ok(post_result)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
+	let voter = context.require_voter(content.vote_token.as_deref())?;
+	check_voting_open(VoteSection::Paper)?;
+	validate_paper_json(&content.paper_json)?;
+	let submit_json = PaperSubmitRest {
+		meta: generate_submit_metadata(&voter.vote_id, context),
+		papers_json: content.paper_json.clone()
+	};
+	let post_result: EmptyJSON = request_upstream(format!("{}/v1/paper/", SUBMIT_HANDLER()), submit_json).await?;
+	Ok(true)
+}
+
+pub async fn submitPaperVoteTyped_impl(context: &Context, content: &PaperSubmitTypedGQL) -> FieldResult<bool> {
+	context.require_voter(content.vote_token.as_deref())?;
+	let mut paper = serde_json::Map::new();
+	let mut issues = vec![];
+	for answer in content.answers.iter() {
+		if paper.contains_key(&answer.id) {
+			issues.push(ValidationIssue::new(answer.id.as_str(), "DUPLICATE", "question is answered more than once"));
+			continue;
+		}
+		match answer.to_json() {
+			Ok(value) => { paper.insert(answer.id.clone(), value); },
+			Err(issue) => issues.push(issue)
+		}
 	}
-}
-
-pub async fn getSubmitPaperVote_impl(context: &Context, vote_token: String) -> FieldResult<PaperSubmitRestQuery> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&vote_token, Some(options));
-	if let Ok(claim) = result {
-		let query_json = QuerySubmitRest {
-			vote_id: claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?
-		};
-		let post_result: PaperSubmitRestQuery = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/get-paper/", SUBMIT_HANDLER), query_json).await?;
-		Ok(post_result)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
-	}
-}
-
-pub async fn getVotingStatus_impl(context: &Context, vote_token: String) -> FieldResult<VotingStatus> {
-	let mut options = VerificationOptions::default();
-	options.allowed_audiences = Some(HashSet::from_strings(&["vote"]));
-	let result = context.public_key.public_key().verify_token::<VoteTokenClaim>(&vote_token, Some(options));
-	if let Ok(claim) = result {
-		let query_json = QuerySubmitRest {
-			vote_id: claim.custom.vote_id.ok_or(ServiceError::new_jwt_error(SERVICE_NAME, None))?
-		};
-		let post_result: VotingStatus = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/voting-status/", SUBMIT_HANDLER), query_json).await?;
-		Ok(post_result)
-	} else {
-		return Err(ServiceError::new_jwt_error(SERVICE_NAME, None).into_field_error());
+	if !issues.is_empty() {
+		return Err(validation_error("INVALID_PAPER", "paper does not match the questionnaire", &issues));
 	}
+	let content = PaperSubmitGQL {
+		vote_token: content.vote_token.clone(),
+		paper_json: serde_json::Value::Object(paper).to_string()
+	};
+	submitPaperVote_impl(context, &content).await
+}
+
+pub async fn getSubmitCharacterVote_impl(context: &Context, vote_token: Option<String>) -> FieldResult<CharacterSubmitRestQuery> {
+	let voter = context.require_voter(vote_token.as_deref())?;
+	let query_json = QuerySubmitRest {
+		vote_id: voter.vote_id
+	};
+	let post_result: CharacterSubmitRestQuery = request_upstream(format!("{}/v1/get-character/", SUBMIT_HANDLER()), query_json).await?;
+	Ok(post_result)
+}
+
+pub async fn getSubmitMusicVote_impl(context: &Context, vote_token: Option<String>) -> FieldResult<MusicSubmitRestQuery> {
+	let voter = context.require_voter(vote_token.as_deref())?;
+	let query_json = QuerySubmitRest {
+		vote_id: voter.vote_id
+	};
+	let post_result: MusicSubmitRestQuery = request_upstream(format!("{}/v1/get-music/", SUBMIT_HANDLER()), query_json).await?;
+	Ok(post_result)
+}
+
+pub async fn getSubmitWorkVote_impl(context: &Context, vote_token: Option<String>) -> FieldResult<WorkSubmitRestQuery> {
+	let voter = context.require_voter(vote_token.as_deref())?;
+	let query_json = QuerySubmitRest {
+		vote_id: voter.vote_id
+	};
+	let post_result: WorkSubmitRestQuery = request_upstream(format!("{}/v1/get-work/", SUBMIT_HANDLER()), query_json).await?;
+	Ok(post_result)
+}
+
+pub async fn getSubmitCPVote_impl(context: &Context, vote_token: Option<String>) -> FieldResult<CPSubmitRestQuery> {
+	let voter = context.require_voter(vote_token.as_deref())?;
+	let query_json = QuerySubmitRest {
+		vote_id: voter.vote_id
+	};
+	let post_result: CPSubmitRestQuery = request_upstream(format!("{}/v1/get-cp/", SUBMIT_HANDLER()), query_json).await?;
+	Ok(post_result)
+}
+
+pub async fn getSubmitPaperVote_impl(context: &Context, vote_token: Option<String>) -> FieldResult<PaperSubmitRestQuery> {
+	let voter = context.require_voter(vote_token.as_deref())?;
+	let query_json = QuerySubmitRest {
+		vote_id: voter.vote_id
+	};
+	let post_result: PaperSubmitRestQuery = request_upstream(format!("{}/v1/get-paper/", SUBMIT_HANDLER()), query_json).await?;
+	Ok(post_result)
+}
+
+pub async fn getVotingStatus_impl(context: &Context, vote_token: Option<String>) -> FieldResult<VotingStatus> {
+	let voter = context.require_voter(vote_token.as_deref())?;
+	let query_json = QuerySubmitRest {
+		vote_id: voter.vote_id
+	};
+	let post_result: VotingStatus = request_upstream(format!("{}/v1/voting-status/", SUBMIT_HANDLER()), query_json).await?;
+	Ok(post_result)
 }
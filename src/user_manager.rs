@@ -3,9 +3,10 @@ use juniper::graphql_value;
 
 use juniper::FieldResult;
 use pvrustlib::EmptyJSON;
-use pvrustlib::json_request_gateway;
 
+use crate::auth::{SESSION_TOKEN_COOKIE, VOTE_TOKEN_COOKIE, removal_cookie, token_cookie};
 use crate::common::SERVICE_NAME;
+use crate::config::config;
 use crate::context::Context;
 use crate::submit_handler::VotingStatus;
 
@@ -22,6 +23,13 @@ pub struct UserEventMeta {
 	pub additional_fingureprint: Option<String>
 }
 
+pub fn generate_user_event_meta(context: &Context) -> UserEventMeta {
+	UserEventMeta {
+		user_ip: context.user_ip.clone(),
+		additional_fingureprint: context.additional_fingureprint.clone()
+	}
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SendPhoneVerifyCodeRequest {
 	pub phone: String,
@@ -105,10 +113,10 @@ pub struct Voter {
 pub struct LoginResults {
 	/// 用户
 	pub user: Voter,
-	/// 投票token
-	pub vote_token: String,
-	/// 用户登录token
-	pub session_token: String
+	/// 投票token，启用 cookie 时为空
+	pub vote_token: Option<String>,
+	/// 用户登录token，启用 cookie 时为空
+	pub session_token: Option<String>
 }
 
 // ------------------------------------------------
@@ -117,17 +125,28 @@ pub struct LoginResults {
 
 use crate::services::*;
 
+/// 启用 cookie 时把token写入 HttpOnly cookie，不再出现在返回值中
+fn deliver_login_tokens(context: &Context, mut result: LoginResults) -> LoginResults {
+	if config().session_cookie.enabled {
+		if let Some(vote_token) = result.vote_token.take() {
+			context.set_cookie(token_cookie(VOTE_TOKEN_COOKIE, vote_token));
+		}
+		if let Some(session_token) = result.session_token.take() {
+			context.set_cookie(token_cookie(SESSION_TOKEN_COOKIE, session_token));
+		}
+	}
+	result
+}
+
 /// 老用户使用email帐号登录
 pub async fn login_email_password(context: &Context, email: String, password: String) -> FieldResult<LoginResults> {
 	let submit_json = EmailLoginInputsForExistingVoters {
 		email: email,
 		password: password,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	Ok(json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/login-email-password", USER_MANAGER), submit_json).await?)
+	let result: LoginResults = request_upstream(format!("{}/v1/login-email-password", USER_MANAGER()), submit_json).await?;
+	Ok(deliver_login_tokens(context, result))
 }
 
 /// 新用户使用email帐号登录
@@ -136,23 +155,18 @@ pub async fn login_email(context: &Context,  email: String, nickname: Option<Str
 		email: email,
 		verify_code: verify_code,
 		nickname: nickname,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	Ok(json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/login-email", USER_MANAGER), submit_json).await?)
+	let result: LoginResults = request_upstream(format!("{}/v1/login-email", USER_MANAGER()), submit_json).await?;
+	Ok(deliver_login_tokens(context, result))
 }
 /// 向邮箱发送验证码
 pub async fn request_email_code(context: &Context, email: String) -> FieldResult<bool> {
 	let submit_json = SendEmailVerifyCodeRequest {
 		email: email,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/send-email-code", USER_MANAGER), submit_json).await?;
+	let t: EmptyJSON = request_upstream(format!("{}/v1/send-email-code", USER_MANAGER()), submit_json).await?;
 	Ok(true)
 }
 
@@ -162,23 +176,18 @@ pub async fn login_phone(context: &Context, phone: String, nickname: Option<Stri
 		phone: phone,
 		verify_code: verify_code,
 		nickname: nickname,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	Ok(json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/login-phone", USER_MANAGER), submit_json).await?)
+	let result: LoginResults = request_upstream(format!("{}/v1/login-phone", USER_MANAGER()), submit_json).await?;
+	Ok(deliver_login_tokens(context, result))
 }
 /// 向手机发送验证码
 pub async fn request_phone_code(context: &Context, phone: String) -> FieldResult<bool> {
 	let submit_json = SendPhoneVerifyCodeRequest {
 		phone: phone,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	let t: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/send-sms-code", USER_MANAGER), submit_json).await?;
+	let t: EmptyJSON = request_upstream(format!("{}/v1/send-sms-code", USER_MANAGER()), submit_json).await?;
 	Ok(true)
 }
 
@@ -227,6 +236,12 @@ pub struct TokenStatusOutput {
 	pub papers_json: Option<String>
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LogoutRequest {
+	pub user_token: String,
+	pub meta: UserEventMeta
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RemoveVoterRequest {
 	pub user_token: String,
@@ -234,58 +249,50 @@ pub struct RemoveVoterRequest {
     pub meta: UserEventMeta
 }
 
-pub async fn update_email(context: &Context, user_token: String, email: String, verify_code: String) -> FieldResult<bool> {
+pub async fn update_email(context: &Context, user_token: Option<String>, email: String, verify_code: String) -> FieldResult<bool> {
+	let user_token = context.require_session_token(user_token)?;
 	let submit_json = UpdateEmailInputs {
 		email: email,
 		verify_code: verify_code,
 		user_token: user_token,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	let t: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/update-email", USER_MANAGER), submit_json).await?;
+	let t: EmptyJSON = request_upstream(format!("{}/v1/update-email", USER_MANAGER()), submit_json).await?;
 	Ok(true)
 }
 
-pub async fn update_phone(context: &Context, user_token: String, phone: String, verify_code: String) -> FieldResult<bool> {
+pub async fn update_phone(context: &Context, user_token: Option<String>, phone: String, verify_code: String) -> FieldResult<bool> {
+	let user_token = context.require_session_token(user_token)?;
 	let submit_json = UpdatePhoneInputs {
 		phone: phone,
 		verify_code: verify_code,
 		user_token: user_token,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	let t: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/update-phone", USER_MANAGER), submit_json).await?;
+	let t: EmptyJSON = request_upstream(format!("{}/v1/update-phone", USER_MANAGER()), submit_json).await?;
 	Ok(true)
 }
 
-pub async fn update_nickname(context: &Context, user_token: String, new_nickname: String) -> FieldResult<bool> {
+pub async fn update_nickname(context: &Context, user_token: Option<String>, new_nickname: String) -> FieldResult<bool> {
+	let user_token = context.require_session_token(user_token)?;
 	let submit_json = UpdateNicknameInputs {
 		nickname: new_nickname,
 		user_token: user_token,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	let t: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/update-nickname", USER_MANAGER), submit_json).await?;
+	let t: EmptyJSON = request_upstream(format!("{}/v1/update-nickname", USER_MANAGER()), submit_json).await?;
 	Ok(true)
 }
 
-pub async fn update_password(context: &Context, user_token: String, old_password: Option<String>, new_password: String) -> FieldResult<bool> {
+pub async fn update_password(context: &Context, user_token: Option<String>, old_password: Option<String>, new_password: String) -> FieldResult<bool> {
+	let user_token = context.require_session_token(user_token)?;
 	let submit_json = UpdatePasswordInputs {
 		old_password: old_password,
 		new_password: new_password,
 		user_token: user_token,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	let t: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/update-password", USER_MANAGER), submit_json).await?;
+	let t: EmptyJSON = request_upstream(format!("{}/v1/update-password", USER_MANAGER()), submit_json).await?;
 	Ok(true)
 }
 
@@ -294,20 +301,34 @@ pub async fn user_token_status(user_token: String, vote_token: Option<String>) -
 		user_token: user_token,
 		vote_token: vote_token
 	};
-	let t: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/user-token-status", USER_MANAGER), submit_json).await?;
+	let t: EmptyJSON = request_upstream(format!("{}/v1/user-token-status", USER_MANAGER()), submit_json).await?;
 	Ok(true)
 }
 
-pub async fn remove_voter(context: &Context, user_token: String, old_password: Option<String>) -> FieldResult<bool> {
+pub async fn remove_voter(context: &Context, user_token: Option<String>, old_password: Option<String>) -> FieldResult<bool> {
+	let user_token = context.require_session_token(user_token)?;
 	let submit_json = RemoveVoterRequest {
 		old_password: old_password,
 		user_token: user_token,
-		meta: UserEventMeta {
-			user_ip: context.user_ip.clone(),
-			additional_fingureprint: context.additional_fingureprint.clone()
-		}
+		meta: generate_user_event_meta(context)
 	};
-	let t: EmptyJSON = json_request_gateway(SERVICE_NAME, &format!("http://{}/v1/remove-voter", USER_MANAGER), submit_json).await?;
+	let t: EmptyJSON = request_upstream(format!("{}/v1/remove-voter", USER_MANAGER()), submit_json).await?;
 	Ok(true)
 }
 
+/// 退出登录，总是清除token cookie，带有登录token时再通知用户服务使其失效
+pub async fn logout(context: &Context, user_token: Option<String>) -> FieldResult<bool> {
+	context.set_cookie(removal_cookie(VOTE_TOKEN_COOKIE));
+	context.set_cookie(removal_cookie(SESSION_TOKEN_COOKIE));
+	let user_token = match context.require_session_token(user_token) {
+		Ok(user_token) => user_token,
+		// cookie 已失效或无法读取，前端也无法删除 HttpOnly cookie，清除即可
+		Err(_) => return Ok(true)
+	};
+	let submit_json = LogoutRequest {
+		user_token: user_token,
+		meta: generate_user_event_meta(context)
+	};
+	let t: EmptyJSON = request_upstream(format!("{}/v1/logout", USER_MANAGER()), submit_json).await?;
+	Ok(true)
+}
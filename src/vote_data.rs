@@ -1,18 +1,47 @@
 
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 
-use juniper::FieldResult;
+use juniper::{FieldError, FieldResult};
+use once_cell::sync::OnceCell;
+use pinyin::ToPinyin;
 use serde_derive::{Serialize, Deserialize};
 
+use crate::common::gateway_error;
+use crate::config::config;
 use crate::context::Context;
+use crate::i18n::{Language, LocalizedName};
+use crate::result_query::PageInfo;
+
+static CATALOG: OnceCell<RwLock<Arc<Catalog>>> = OnceCell::new();
+
+/// 检查投票数据文件是否变化的间隔
+const CATALOG_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 搜索每页默认的条目数
+const DEFAULT_SEARCH_PAGE_SIZE: i32 = 20;
+/// 搜索每页最多的条目数
+const MAX_SEARCH_PAGE_SIZE: i32 = 100;
+/// 搜索关键词最大长度
+const MAX_SEARCH_QUERY_LENGTH: usize = 50;
 
 #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(context = Context)]
 pub struct VotableCharacters {
+    /// 投票数据版本
+    pub version: String,
     pub data: Vec<VotableCharacter>
 }
 
-#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VotableCharacter {
+    /// 人物ID，提交时使用
+    pub id: String,
     pub name: String,
+    /// 各语言的名称，缺少时使用 name
+    #[serde(default)]
+    pub names: LocalizedName,
     pub altnames: Vec<String>,
     pub title: Option<String>,
     pub image: String,
@@ -22,13 +51,21 @@ pub struct VotableCharacter {
 }
 
 #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(context = Context)]
 pub struct VotableMusics {
+    /// 投票数据版本
+    pub version: String,
     pub data: Vec<VotableMusic>
 }
 
-#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VotableMusic {
+    /// 音乐ID，提交时使用
+    pub id: String,
     pub name: String,
+    /// 各语言的名称，缺少时使用 name
+    #[serde(default)]
+    pub names: LocalizedName,
     pub altnames: Vec<String>,
     pub image: String,
     /// 首次出场的作品
@@ -38,57 +75,591 @@ pub struct VotableMusic {
 
 
 #[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[graphql(context = Context)]
 pub struct VotableWorks {
+    /// 投票数据版本
+    pub version: String,
     pub data: Vec<VotableWork>
 }
 
-#[derive(juniper::GraphQLObject, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VotableWork {
+    /// 作品ID，提交时使用
+    pub id: String,
     pub name: String,
+    /// 各语言的名称，缺少时使用 name
+    #[serde(default)]
+    pub names: LocalizedName,
     pub altnames: Vec<String>,
     pub image: String,
     /// 发布日期
     pub release_date: String
 }
 
-pub async fn listVotableCharacters_impl(context: &Context) -> FieldResult<VotableCharacters> {
-	Ok(VotableCharacters {
-        data: vec![
-            VotableCharacter {
-                name: "博丽灵梦".to_string(),
-                altnames: vec!["城管".to_string(), "红白".to_string(), "reimu".to_string()],
-                title: Some("乐园的可爱巫女".to_string()),
-                image: "none".to_string(),
-                color: "FF0000".to_string(),
-                appeared_in: vec!["东方虹龙洞".to_string(), "等等".to_string()]
+/// 投票数据文件，所有引用（出场作品、曲目对应人物等）使用ID
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    pub version: String,
+    pub characters: Vec<VotableCharacter>,
+    pub musics: Vec<VotableMusic>,
+    pub works: Vec<VotableWork>,
+    #[serde(skip)]
+    character_index: HashMap<String, usize>,
+    #[serde(skip)]
+    music_index: HashMap<String, usize>,
+    #[serde(skip)]
+    work_index: HashMap<String, usize>,
+    /// 作品ID -> 出场人物
+    #[serde(skip)]
+    work_characters: HashMap<String, Vec<usize>>,
+    /// 作品ID -> 首次出场的音乐
+    #[serde(skip)]
+    work_musics: HashMap<String, Vec<usize>>,
+    /// 每个条目用于搜索的关键词，包括名字、别名及其拼音和罗马字
+    #[serde(skip)]
+    character_keys: Vec<Vec<String>>,
+    #[serde(skip)]
+    music_keys: Vec<Vec<String>>,
+    #[serde(skip)]
+    work_keys: Vec<Vec<String>>
+}
+
+fn build_index<'a>(kind: &str, ids: impl Iterator<Item = &'a String>) -> Result<HashMap<String, usize>, String> {
+    let mut index = HashMap::new();
+    for (i, id) in ids.enumerate() {
+        if id.is_empty() {
+            return Err(format!("{} #{} has an empty id", kind, i));
+        }
+        if index.insert(id.clone(), i).is_some() {
+            return Err(format!("duplicate {} id {:?}", kind, id));
+        }
+    }
+    Ok(index)
+}
+
+impl Catalog {
+    pub fn from_file(path: &str) -> Result<Catalog, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+        let mut catalog: Catalog = serde_json::from_str(&content).map_err(|e| format!("failed to parse {}: {}", path, e))?;
+        catalog.character_index = build_index("character", catalog.characters.iter().map(|x| &x.id))?;
+        catalog.music_index = build_index("music", catalog.musics.iter().map(|x| &x.id))?;
+        catalog.work_index = build_index("work", catalog.works.iter().map(|x| &x.id))?;
+        catalog.check_references()?;
+        for (i, c) in catalog.characters.iter().enumerate() {
+            for w in c.appeared_in.iter() {
+                catalog.work_characters.entry(w.clone()).or_insert_with(Vec::new).push(i);
             }
-        ]
-    })
+        }
+        for (i, m) in catalog.musics.iter().enumerate() {
+            catalog.work_musics.entry(m.first_appeared_in.clone()).or_insert_with(Vec::new).push(i);
+        }
+        catalog.character_keys = catalog.characters.iter().map(|x| search_keys(&x.name, &x.names, &x.altnames)).collect();
+        catalog.music_keys = catalog.musics.iter().map(|x| search_keys(&x.name, &x.names, &x.altnames)).collect();
+        catalog.work_keys = catalog.works.iter().map(|x| search_keys(&x.name, &x.names, &x.altnames)).collect();
+        Ok(catalog)
+    }
+
+    /// 检查所有引用的ID是否存在
+    fn check_references(&self) -> Result<(), String> {
+        for c in self.characters.iter() {
+            if let Some(w) = c.appeared_in.iter().find(|w| !self.work_index.contains_key(*w)) {
+                return Err(format!("character {:?} appeared in unknown work {:?}", c.id, w));
+            }
+        }
+        for m in self.musics.iter() {
+            if !self.work_index.contains_key(&m.first_appeared_in) {
+                return Err(format!("music {:?} first appeared in unknown work {:?}", m.id, m.first_appeared_in));
+            }
+            if let Some(c) = &m.character {
+                if !self.character_index.contains_key(c) {
+                    return Err(format!("music {:?} refers to unknown character {:?}", m.id, c));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn character(&self, id: &str) -> Option<&VotableCharacter> {
+        self.character_index.get(id).map(|i| &self.characters[*i])
+    }
+
+    pub fn music(&self, id: &str) -> Option<&VotableMusic> {
+        self.music_index.get(id).map(|i| &self.musics[*i])
+    }
+
+    pub fn work(&self, id: &str) -> Option<&VotableWork> {
+        self.work_index.get(id).map(|i| &self.works[*i])
+    }
+
+    pub fn characters_in_work(&self, id: &str) -> Vec<VotableCharacter> {
+        self.work_characters.get(id).map_or(vec![], |x| x.iter().map(|i| self.characters[*i].clone()).collect())
+    }
+
+    pub fn musics_in_work(&self, id: &str) -> Vec<VotableMusic> {
+        self.work_musics.get(id).map_or(vec![], |x| x.iter().map(|i| self.musics[*i].clone()).collect())
+    }
 }
 
-pub async fn listVotableWorks_impl(context: &Context) -> FieldResult<VotableWorks> {
-	Ok(VotableWorks {
-        data: vec![
-            VotableWork {
-                name: "东方虹龙洞".to_string(),
-                altnames: vec!["um".to_string(), "Unconnected Marketeers".to_string()],
-                image: "none".to_string(),
-                release_date: "2021-05-04".to_string()
+// ------------------------------------------------
+// GQL Schemas
+// ------------------------------------------------
+
+#[juniper::graphql_object(Context = Context, description = "可投票的人物")]
+impl VotableCharacter {
+    /// 人物ID，提交时使用
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    /// 名称，lang 未指定时使用请求协商的语言
+    pub fn name(&self, context: &Context, lang: Option<Language>) -> &str {
+        self.names.resolve(lang.unwrap_or(context.lang), &self.name)
+    }
+    /// 各语言的名称
+    pub fn names(&self) -> &LocalizedName {
+        &self.names
+    }
+    pub fn altnames(&self) -> &Vec<String> {
+        &self.altnames
+    }
+    pub fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+    pub fn color(&self) -> &str {
+        &self.color
+    }
+    /// 出场作品ID
+    pub fn appeared_in(&self) -> &Vec<String> {
+        &self.appeared_in
+    }
+    /// 出场作品
+    pub fn works(&self) -> Vec<VotableWork> {
+        let catalog = catalog();
+        self.appeared_in.iter().filter_map(|w| catalog.work(w).cloned()).collect()
+    }
+    /// 相关音乐
+    pub fn musics(&self) -> Vec<VotableMusic> {
+        catalog().musics.iter().filter(|m| m.character.as_ref() == Some(&self.id)).cloned().collect()
+    }
+}
+
+#[juniper::graphql_object(Context = Context, description = "可投票的音乐")]
+impl VotableMusic {
+    /// 音乐ID，提交时使用
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    /// 名称，lang 未指定时使用请求协商的语言
+    pub fn name(&self, context: &Context, lang: Option<Language>) -> &str {
+        self.names.resolve(lang.unwrap_or(context.lang), &self.name)
+    }
+    /// 各语言的名称
+    pub fn names(&self) -> &LocalizedName {
+        &self.names
+    }
+    pub fn altnames(&self) -> &Vec<String> {
+        &self.altnames
+    }
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+    /// 首次出场的作品ID
+    pub fn first_appeared_in(&self) -> &str {
+        &self.first_appeared_in
+    }
+    /// 对应人物ID
+    pub fn character_id(&self) -> Option<String> {
+        self.character.clone()
+    }
+    /// 首次出场的作品
+    pub fn work(&self) -> Option<VotableWork> {
+        catalog().work(&self.first_appeared_in).cloned()
+    }
+    /// 对应人物
+    pub fn character(&self) -> Option<VotableCharacter> {
+        self.character.as_ref().and_then(|c| catalog().character(c).cloned())
+    }
+}
+
+#[juniper::graphql_object(Context = Context, description = "可投票的作品")]
+impl VotableWork {
+    /// 作品ID，提交时使用
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    /// 名称，lang 未指定时使用请求协商的语言
+    pub fn name(&self, context: &Context, lang: Option<Language>) -> &str {
+        self.names.resolve(lang.unwrap_or(context.lang), &self.name)
+    }
+    /// 各语言的名称
+    pub fn names(&self) -> &LocalizedName {
+        &self.names
+    }
+    pub fn altnames(&self) -> &Vec<String> {
+        &self.altnames
+    }
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+    /// 发布日期
+    pub fn release_date(&self) -> &str {
+        &self.release_date
+    }
+    /// 出场人物
+    pub fn characters(&self) -> Vec<VotableCharacter> {
+        catalog().characters_in_work(&self.id)
+    }
+    /// 首次出场的音乐
+    pub fn musics(&self) -> Vec<VotableMusic> {
+        catalog().musics_in_work(&self.id)
+    }
+}
+
+// ------------------------------------------------
+// Search
+// ------------------------------------------------
+
+/// 统一大小写，去掉空白和标点
+fn normalize_search_text(s: &str) -> String {
+    s.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+}
+
+fn is_kana(c: char) -> bool {
+    ('\u{3040}'..='\u{30ff}').contains(&c)
+}
+
+fn search_keys(name: &str, names: &LocalizedName, altnames: &[String]) -> Vec<String> {
+    let mut keys = vec![];
+    for s in std::iter::once(name).chain(names.all().map(|x| x.as_str())).chain(altnames.iter().map(|x| x.as_str())) {
+        keys.push(normalize_search_text(s));
+        // 汉字的全拼和首字母，例如 博丽灵梦 -> bolilingmeng, bllm
+        let pinyin: Vec<_> = s.to_pinyin().flatten().collect();
+        if !pinyin.is_empty() {
+            keys.push(pinyin.iter().map(|p| p.plain()).collect());
+            keys.push(pinyin.iter().map(|p| p.first_letter()).collect());
+        }
+        // 假名的罗马字，例如 れいむ -> reimu
+        if s.chars().any(is_kana) {
+            keys.push(normalize_search_text(&wana_kana::to_romaji::to_romaji(s)));
+        }
+    }
+    keys.retain(|k| !k.is_empty());
+    keys.dedup();
+    keys
+}
+
+/// 模糊匹配得分，越大越相关：完全相同 > 前缀 > 包含 > 按顺序包含所有字符
+fn match_score(query: &str, keys: &[String]) -> Option<u32> {
+    keys.iter().filter_map(|key| {
+        if key == query {
+            Some(4)
+        } else if key.starts_with(query) {
+            Some(3)
+        } else if key.contains(query) {
+            Some(2)
+        } else {
+            let mut chars = key.chars();
+            if query.chars().all(|q| chars.any(|c| c == q)) { Some(1) } else { None }
+        }
+    }).max()
+}
+
+/// 按相关度排序（相同得分保持数据文件中的顺序），query 为空时返回全部
+fn search<'a, T>(items: &'a [T], keys: &[Vec<String>], query: &Option<String>, filter: impl Fn(&T) -> bool) -> Vec<&'a T> {
+    let query = query.as_ref().map(|q| normalize_search_text(q)).filter(|q| !q.is_empty());
+    let mut matched: Vec<(u32, &T)> = items.iter().zip(keys.iter()).filter(|(item, _)| filter(*item)).filter_map(|(item, keys)| {
+        match &query {
+            Some(q) => match_score(q, keys).map(|score| (score, item)),
+            None => Some((0, item))
+        }
+    }).collect();
+    matched.sort_by(|a, b| b.0.cmp(&a.0));
+    matched.into_iter().map(|(_, item)| item).collect()
+}
+
+fn invalid_search(message: &str) -> FieldError {
+    gateway_error("INVALID_SEARCH_QUERY", message)
+}
+
+/// 在搜索前校验参数，返回 (起始位置, 数量)。游标格式为 数据版本:位置，数据重新加载后旧的游标失效
+fn page_request(version: &str, query: &Option<String>, first: Option<i32>, after: &Option<String>) -> FieldResult<(usize, usize)> {
+    if let Some(q) = query {
+        if q.chars().count() > MAX_SEARCH_QUERY_LENGTH {
+            return Err(invalid_search(&format!("query must not exceed {} characters", MAX_SEARCH_QUERY_LENGTH)));
+        }
+    }
+    let limit = first.unwrap_or(DEFAULT_SEARCH_PAGE_SIZE);
+    if limit < 1 || limit > MAX_SEARCH_PAGE_SIZE {
+        return Err(invalid_search(&format!("first must be between 1 and {}", MAX_SEARCH_PAGE_SIZE)));
+    }
+    let start = match after {
+        Some(cursor) => {
+            let position = cursor.strip_prefix(version).and_then(|c| c.strip_prefix(':')).and_then(|c| c.parse::<usize>().ok());
+            match position.and_then(|p| p.checked_add(1)) {
+                Some(p) => p,
+                None => return Err(gateway_error("INVALID_CURSOR", "cursor is invalid or the catalog has been updated"))
             }
-        ]
-    })
+        },
+        None => 0
+    };
+    Ok((start, limit as usize))
+}
+
+fn page_range(total: usize, (start, limit): (usize, usize)) -> (usize, usize) {
+    let start = start.min(total);
+    (start, start.saturating_add(limit).min(total))
+}
+
+fn page_info(version: &str, total: usize, start: usize, end: usize) -> PageInfo {
+    PageInfo {
+        has_next_page: end < total,
+        has_previous_page: start > 0,
+        start_cursor: if start < end { Some(format!("{}:{}", version, start)) } else { None },
+        end_cursor: if start < end { Some(format!("{}:{}", version, end - 1)) } else { None }
+    }
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(context = Context, description="人物搜索结果中的一项")]
+pub struct VotableCharacterEdge {
+    /// 分页游标
+    pub cursor: String,
+    pub node: VotableCharacter
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(context = Context, description="人物搜索结果分页")]
+pub struct VotableCharacterConnection {
+    /// 投票数据版本
+    pub version: String,
+    pub edges: Vec<VotableCharacterEdge>,
+    pub page_info: PageInfo,
+    /// 满足条件的人物总数
+    pub total_count: i32
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(context = Context, description="音乐搜索结果中的一项")]
+pub struct VotableMusicEdge {
+    /// 分页游标
+    pub cursor: String,
+    pub node: VotableMusic
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(context = Context, description="音乐搜索结果分页")]
+pub struct VotableMusicConnection {
+    /// 投票数据版本
+    pub version: String,
+    pub edges: Vec<VotableMusicEdge>,
+    pub page_info: PageInfo,
+    /// 满足条件的音乐总数
+    pub total_count: i32
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(context = Context, description="作品搜索结果中的一项")]
+pub struct VotableWorkEdge {
+    /// 分页游标
+    pub cursor: String,
+    pub node: VotableWork
+}
+
+#[derive(juniper::GraphQLObject, Clone)]
+#[graphql(context = Context, description="作品搜索结果分页")]
+pub struct VotableWorkConnection {
+    /// 投票数据版本
+    pub version: String,
+    pub edges: Vec<VotableWorkEdge>,
+    pub page_info: PageInfo,
+    /// 满足条件的作品总数
+    pub total_count: i32
+}
+
+pub fn init(catalog: Catalog) {
+    if CATALOG.set(RwLock::new(Arc::new(catalog))).is_err() {
+        panic!("catalog already initialized");
+    }
+}
+
+/// 当前的投票数据，重新加载后旧的快照仍然有效
+pub fn catalog() -> Arc<Catalog> {
+    CATALOG.get().expect("catalog not initialized").read().unwrap().clone()
+}
+
+/// 重新读取投票数据文件，失败时保留原来的数据
+pub fn reload_catalog() {
+    let path = &config().catalog_path;
+    match Catalog::from_file(path) {
+        Ok(catalog) => {
+            log::info!("catalog {} reloaded, version {}", path, catalog.version);
+            *CATALOG.get().expect("catalog not initialized").write().unwrap() = Arc::new(catalog);
+        },
+        Err(e) => log::warn!("failed to reload catalog, keeping version {}: {}", catalog().version, e)
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// 文件修改或收到 SIGHUP 时重新加载投票数据
+pub async fn watch_catalog() {
+    let path = config().catalog_path.clone();
+    let mut last_modified = modified_time(&path);
+    let mut interval = tokio::time::interval(CATALOG_POLL_INTERVAL);
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).expect("failed to listen for SIGHUP");
+    loop {
+        #[cfg(unix)]
+        let reload = tokio::select! {
+            _ = interval.tick() => false,
+            _ = hangup.recv() => true
+        };
+        #[cfg(not(unix))]
+        let reload = { interval.tick().await; false };
+        let modified = modified_time(&path);
+        if reload || modified != last_modified {
+            last_modified = modified;
+            reload_catalog();
+        }
+    }
+}
+
+pub fn is_votable_character(id: &str) -> bool {
+    catalog().character(id).is_some()
+}
+
+pub fn is_votable_work(id: &str) -> bool {
+    catalog().work(id).is_some()
+}
+
+pub fn is_votable_music(id: &str) -> bool {
+    catalog().music(id).is_some()
+}
+
+pub async fn listVotableCharacters_impl(context: &Context) -> FieldResult<VotableCharacters> {
+    let catalog = catalog();
+	Ok(VotableCharacters { version: catalog.version.clone(), data: catalog.characters.clone() })
+}
+
+pub async fn listVotableWorks_impl(context: &Context) -> FieldResult<VotableWorks> {
+    let catalog = catalog();
+	Ok(VotableWorks { version: catalog.version.clone(), data: catalog.works.clone() })
 }
 
 pub async fn listVotableMusics_impl(context: &Context) -> FieldResult<VotableMusics> {
-	Ok(VotableMusics {
-        data: vec![
-            VotableMusic {
-                name: "大吉猫咪".to_string(),
-                altnames: vec!["Kitten of Great Fortune".to_string(), "大吉キトゥン".to_string()],
-                image: "none".to_string(),
-                first_appeared_in: "东方虹龙洞".to_string(),
-                character: Some("豪德寺三花".to_string())
-            }
-        ]
+    let catalog = catalog();
+	Ok(VotableMusics { version: catalog.version.clone(), data: catalog.musics.clone() })
+}
+
+pub async fn searchVotableCharacters_impl(context: &Context, query: Option<String>, work: Option<String>, first: Option<i32>, after: Option<String>) -> FieldResult<VotableCharacterConnection> {
+    let catalog = catalog();
+    let page = page_request(&catalog.version, &query, first, &after)?;
+    let matched = search(&catalog.characters, &catalog.character_keys, &query, |c| work.as_ref().map_or(true, |w| c.appeared_in.contains(w)));
+    let (start, end) = page_range(matched.len(), page);
+    Ok(VotableCharacterConnection {
+        version: catalog.version.clone(),
+        edges: (start..end).map(|i| VotableCharacterEdge { cursor: format!("{}:{}", catalog.version, i), node: matched[i].clone() }).collect(),
+        page_info: page_info(&catalog.version, matched.len(), start, end),
+        total_count: matched.len() as i32
+    })
+}
+
+pub async fn searchVotableMusics_impl(context: &Context, query: Option<String>, work: Option<String>, first: Option<i32>, after: Option<String>) -> FieldResult<VotableMusicConnection> {
+    let catalog = catalog();
+    let page = page_request(&catalog.version, &query, first, &after)?;
+    let matched = search(&catalog.musics, &catalog.music_keys, &query, |m| work.as_ref().map_or(true, |w| &m.first_appeared_in == w));
+    let (start, end) = page_range(matched.len(), page);
+    Ok(VotableMusicConnection {
+        version: catalog.version.clone(),
+        edges: (start..end).map(|i| VotableMusicEdge { cursor: format!("{}:{}", catalog.version, i), node: matched[i].clone() }).collect(),
+        page_info: page_info(&catalog.version, matched.len(), start, end),
+        total_count: matched.len() as i32
+    })
+}
+
+pub async fn searchVotableWorks_impl(context: &Context, query: Option<String>, first: Option<i32>, after: Option<String>) -> FieldResult<VotableWorkConnection> {
+    let catalog = catalog();
+    let page = page_request(&catalog.version, &query, first, &after)?;
+    let matched = search(&catalog.works, &catalog.work_keys, &query, |_| true);
+    let (start, end) = page_range(matched.len(), page);
+    Ok(VotableWorkConnection {
+        version: catalog.version.clone(),
+        edges: (start..end).map(|i| VotableWorkEdge { cursor: format!("{}:{}", catalog.version, i), node: matched[i].clone() }).collect(),
+        page_info: page_info(&catalog.version, matched.len(), start, end),
+        total_count: matched.len() as i32
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(name: &str) -> Vec<String> {
+        search_keys(name, &LocalizedName::default(), &[])
+    }
+
+    fn page(after: Option<&str>, first: Option<i32>, query: Option<&str>) -> FieldResult<(usize, usize)> {
+        page_request("v1", &query.map(|q| q.to_string()), first, &after.map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn scores_matches() {
+        let keys = vec!["hakureireimu".to_string()];
+        assert_eq!(match_score("hakureireimu", &keys), Some(4));
+        assert_eq!(match_score("hakurei", &keys), Some(3));
+        assert_eq!(match_score("reimu", &keys), Some(2));
+        assert_eq!(match_score("hkrm", &keys), Some(1));
+        assert_eq!(match_score("marisa", &keys), None);
+    }
+
+    #[test]
+    fn ranks_exact_prefix_contains_subsequence() {
+        let items = ["Hakurei Reimu", "rxeximxu", "Reimu Hakurei", "Rumia", "Reimu"];
+        let keys = items.iter().map(|name| keys(name)).collect::<Vec<_>>();
+        let matched = search(&items, &keys, &Some("reimu".to_string()), |_| true);
+        assert_eq!(matched, vec![&"Reimu", &"Reimu Hakurei", &"Hakurei Reimu", &"rxeximxu"]);
+        assert_eq!(search(&items, &keys, &None, |_| true).len(), items.len());
+    }
+
+    #[test]
+    fn matches_pinyin_and_initials() {
+        let keys = keys("博丽灵梦");
+        assert_eq!(match_score("bolilingmeng", &keys), Some(4));
+        assert_eq!(match_score("bllm", &keys), Some(4));
+        assert_eq!(match_score("boli", &keys), Some(3));
+    }
+
+    #[test]
+    fn matches_romaji() {
+        let keys = keys("れいむ");
+        assert_eq!(match_score("reimu", &keys), Some(4));
+        assert_eq!(match_score("rei", &keys), Some(3));
+    }
+
+    #[test]
+    fn parses_cursors() {
+        assert_eq!(page(None, None, None).unwrap(), (0, DEFAULT_SEARCH_PAGE_SIZE as usize));
+        assert_eq!(page(Some("v1:3"), Some(5), None).unwrap(), (4, 5));
+        assert!(page(Some("v2:3"), None, None).unwrap_err().message().contains("cursor"));
+        assert!(page(Some("v1:abc"), None, None).unwrap_err().message().contains("cursor"));
+        assert!(page(Some(&format!("v1:{}", usize::MAX)), None, None).unwrap_err().message().contains("cursor"));
+    }
+
+    #[test]
+    fn validates_page_arguments() {
+        assert!(page(None, Some(0), None).is_err());
+        assert!(page(None, Some(MAX_SEARCH_PAGE_SIZE + 1), None).is_err());
+        assert!(page(None, None, Some(&"a".repeat(MAX_SEARCH_QUERY_LENGTH + 1))).is_err());
+    }
+
+    #[test]
+    fn clamps_page_range() {
+        assert_eq!(page_range(5, (4, 10)), (4, 5));
+        assert_eq!(page_range(5, (8, 10)), (5, 5));
+        assert_eq!(page_range(5, (2, usize::MAX)), (2, 5));
+    }
+}